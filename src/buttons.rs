@@ -0,0 +1,201 @@
+use x11::xlib::Time;
+
+/// What a button press or release does to the selection.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ButtonAction {
+  None,
+  /// Start a new selection at the pointer.
+  Restart,
+  /// Move the first corner of the selection to the pointer.
+  MoveAnchor,
+  /// Apply the selection, after restarting it at the pointer if `restart`
+  /// is set.
+  Finish { restart: bool },
+}
+
+/// Tracks the pointer buttons and decides what presses and releases do.
+///
+/// The drag button makes the whole selection follow the pointer while it is
+/// held, releasing the confirm button applies the selection, and the reset
+/// button starts over at the pointer.
+pub struct Buttons {
+  pub confirm: u32,
+  pub drag: u32,
+  pub reset: Option<u32>,
+  /// A click of the confirm button selects the single cell under it.
+  pub click_select: bool,
+  /// The selection follows the pointer until the confirm button is pressed,
+  /// which anchors its first corner.
+  pub hold_to_confirm: bool,
+  /// While this is set the entire selection follows the pointer instead of
+  /// just the second corner.
+  dragging: bool,
+  /// Position and time of the last confirm button press, used to detect
+  /// clicks.
+  confirm_press: Option<(i32, i32, Time)>,
+}
+
+impl Buttons {
+  /// Creates the state with no buttons pressed, or with the drag button held
+  /// if `dragging` is set, for when it was already held when we started.
+  pub fn new (confirm: u32, drag: u32, reset: Option<u32>, dragging: bool) -> Self {
+    Self {
+      confirm,
+      drag,
+      reset,
+      click_select: false,
+      hold_to_confirm: false,
+      dragging,
+      confirm_press: None,
+    }
+  }
+
+  pub fn with_click_select (mut self, click_select: bool) -> Self {
+    self.click_select = click_select;
+    self
+  }
+
+  pub fn with_hold_to_confirm (mut self, hold_to_confirm: bool) -> Self {
+    self.hold_to_confirm = hold_to_confirm;
+    self
+  }
+
+  pub fn press (&mut self, button: u32, x: i32, y: i32, time: Time) -> ButtonAction {
+    if button == self.confirm {
+      self.confirm_press = Some ((x, y, time));
+      if self.hold_to_confirm {
+        return ButtonAction::Restart;
+      }
+    } else if button == self.drag {
+      self.dragging = true;
+      return ButtonAction::MoveAnchor;
+    } else if Some (button) == self.reset {
+      self.dragging = false;
+      self.confirm_press = None;
+      return ButtonAction::Restart;
+    }
+    ButtonAction::None
+  }
+
+  pub fn release (&mut self, button: u32, x: i32, y: i32, time: Time) -> ButtonAction {
+    if button == self.confirm {
+      ButtonAction::Finish {
+        restart: self.click_select && self.is_click (x, y, time),
+      }
+    } else {
+      if button == self.drag {
+        self.dragging = false;
+      }
+      ButtonAction::None
+    }
+  }
+
+  /// Returns whether the first corner of the selection follows the pointer
+  /// along with the second one.
+  pub fn anchor_follows (&self) -> bool {
+    self.dragging || (self.hold_to_confirm && self.confirm_press.is_none ())
+  }
+
+  /// Returns whether a confirm button release at the given position and time
+  /// ends a click rather than a drag.
+  fn is_click (&self, x: i32, y: i32, time: Time) -> bool {
+    const MAX_DISTANCE: u32 = 4;
+    const MAX_DURATION: Time = 300;
+    match self.confirm_press {
+      Some ((press_x, press_y, press_time)) => {
+        press_x.abs_diff (x) <= MAX_DISTANCE
+          && press_y.abs_diff (y) <= MAX_DISTANCE
+          // Server time is 32 bits and wraps around every ~49.7 days.
+          && (time as u32).wrapping_sub (press_time as u32) as Time <= MAX_DURATION
+      }
+      None => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use x11::xlib::{Button1, Button2, Button3};
+
+  fn buttons () -> Buttons {
+    Buttons::new (Button1, Button3, Some (Button2), false)
+  }
+
+  #[test]
+  fn drag_moves_the_whole_selection_until_released () {
+    let mut buttons = buttons ();
+    assert! (!buttons.anchor_follows ());
+    assert_eq! (buttons.press (Button3, 10, 10, 0), ButtonAction::MoveAnchor);
+    assert! (buttons.anchor_follows ());
+    assert_eq! (buttons.release (Button3, 50, 50, 100), ButtonAction::None);
+    assert! (!buttons.anchor_follows ());
+  }
+
+  #[test]
+  fn confirm_applies_on_release () {
+    let mut buttons = buttons ();
+    assert_eq! (buttons.press (Button1, 10, 10, 0), ButtonAction::None);
+    assert_eq! (
+      buttons.release (Button1, 10, 10, 50),
+      ButtonAction::Finish { restart: false }
+    );
+    // Confirming while dragging
+    let mut buttons = self::buttons ();
+    buttons.press (Button3, 10, 10, 0);
+    buttons.press (Button1, 20, 20, 10);
+    assert! (buttons.anchor_follows ());
+    assert_eq! (
+      buttons.release (Button1, 20, 20, 20),
+      ButtonAction::Finish { restart: false }
+    );
+  }
+
+  #[test]
+  fn click_select () {
+    let mut buttons = buttons ().with_click_select (true);
+    buttons.press (Button1, 10, 10, 0);
+    assert_eq! (
+      buttons.release (Button1, 12, 8, 100),
+      ButtonAction::Finish { restart: true }
+    );
+    // Moved too far
+    buttons.press (Button1, 10, 10, 0);
+    assert_eq! (
+      buttons.release (Button1, 40, 10, 100),
+      ButtonAction::Finish { restart: false }
+    );
+    // Held too long
+    buttons.press (Button1, 10, 10, 0);
+    assert_eq! (
+      buttons.release (Button1, 10, 10, 1000),
+      ButtonAction::Finish { restart: false }
+    );
+    // The server time wrapped around between press and release
+    buttons.press (Button1, 10, 10, u32::MAX as Time - 50);
+    assert_eq! (
+      buttons.release (Button1, 10, 10, 50),
+      ButtonAction::Finish { restart: true }
+    );
+  }
+
+  #[test]
+  fn hold_to_confirm () {
+    let mut buttons = buttons ().with_hold_to_confirm (true);
+    assert! (buttons.anchor_follows ());
+    assert_eq! (buttons.press (Button1, 10, 10, 0), ButtonAction::Restart);
+    assert! (!buttons.anchor_follows ());
+  }
+
+  #[test]
+  fn reset_cancels_drag_and_confirm () {
+    let mut buttons =
+      Buttons::new (Button1, Button3, Some (Button2), true).with_hold_to_confirm (true);
+    buttons.press (Button1, 10, 10, 0);
+    assert_eq! (buttons.press (Button2, 20, 20, 10), ButtonAction::Restart);
+    // Back to following the pointer until the confirm button is pressed.
+    assert! (buttons.anchor_follows ());
+    buttons.press (Button1, 10, 10, 20);
+    assert! (!buttons.anchor_follows ());
+  }
+}
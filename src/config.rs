@@ -0,0 +1,469 @@
+use super::*;
+
+/// Parsed and validated options for a [`GridResize`].
+///
+/// The target window, dimensions, and monitor are kept as given since they
+/// can only be resolved with a display connection.
+///
+/// Library users create it with [`new`](Self::new) and change the defaults
+/// with the `with_*` methods, the values are validated again by
+/// [`GridResize::new`].
+pub struct Config {
+  pub(crate) window: String,
+  pub(crate) client_walk: bool,
+  pub(crate) dimensions: String,
+  pub(crate) monitor: Option<String>,
+  pub(crate) work_area: bool,
+  pub(crate) margin: u32,
+  /// Empty if `square_cells` is used.
+  pub(crate) column_weights: Vec<u32>,
+  pub(crate) row_weights: Vec<u32>,
+  pub(crate) square_cells: Option<u32>,
+  pub(crate) grid_from_window: Option<XWindow>,
+  pub(crate) initial_selection: Option<((u32, u32), (u32, u32))>,
+  pub(crate) reserve_space: Option<Edge>,
+  pub(crate) activate: bool,
+  pub(crate) keep_open: bool,
+  pub(crate) double_buffer: bool,
+  pub(crate) gap: u32,
+  pub(crate) outer_gap: u32,
+  pub(crate) origin: Origin,
+  pub(crate) aspect_lock: Option<(u32, u32)>,
+  pub(crate) min_cells: (u32, u32),
+  pub(crate) grid_style: GridStyle,
+  pub(crate) method: MoveResizeMethod,
+  pub(crate) format: OutputFormat,
+  pub(crate) line_color: RGB,
+  pub(crate) selection_color: RGB,
+  pub(crate) hover_color: RGB,
+  pub(crate) line_width: f64,
+  /// `None` to detect it from the display.
+  pub(crate) scale: Option<f64>,
+  pub(crate) dim: f64,
+  pub(crate) confirm_button: u32,
+  pub(crate) drag_button: u32,
+  /// `None` if no button resets the selection.
+  pub(crate) reset_button: Option<u32>,
+  pub(crate) right_button_pressed: bool,
+  pub(crate) click_select: bool,
+  pub(crate) hold_to_confirm: bool,
+  pub(crate) quit_on_focus_loss: bool,
+  pub(crate) cursor: c_uint,
+  pub(crate) window_class: String,
+  pub(crate) window_instance: String,
+  pub(crate) opacity: Option<f64>,
+  pub(crate) live: bool,
+  pub(crate) motion_interval: Option<Duration>,
+  pub(crate) timeout: Option<Duration>,
+  pub(crate) respect_hints: bool,
+  pub(crate) center_in_cell: bool,
+  pub(crate) flash: bool,
+  pub(crate) animate_duration: Option<Duration>,
+  /// Pixels or percent of the monitor, resolved when applying the selection.
+  pub(crate) max_width: Option<String>,
+  pub(crate) max_height: Option<String>,
+  pub(crate) dry_run: bool,
+  pub(crate) snap_distance: u32,
+  /// Windows to ignore for snapping and tiling.
+  pub(crate) exclude: Vec<XWindow>,
+  /// Resolved when the selection is applied since `:AUTO:` depends on it.
+  pub(crate) swap_with: Option<String>,
+  pub(crate) unfullscreen: bool,
+  pub(crate) unmaximize: bool,
+  pub(crate) verify: bool,
+  pub(crate) repeat: bool,
+  pub(crate) labels: bool,
+  pub(crate) key_accel: u32,
+  pub(crate) verbose: bool,
+}
+
+impl Config {
+  /// Creates the options for resizing `window` on a grid with the given
+  /// dimensions and cells, using the same formats and defaults as the
+  /// command-line arguments.
+  pub fn new (window: &str, dimensions: &str, cells: &str) -> Result<Self> {
+    Self::try_from (&Args::try_parse_from (["grid-resize", window, dimensions, cells])?)
+  }
+
+  /// Checks the values that can also be changed with the `with_*` methods.
+  pub(crate) fn validate (self) -> Result<Self> {
+    if !(0.0..=1.0).contains (&self.dim) {
+      bail! ("Dim value must be between 0.0 and 1.0");
+    }
+    if self.opacity.is_some_and (|opacity| !(0.0..=1.0).contains (&opacity)) {
+      bail! ("Opacity value must be between 0.0 and 1.0");
+    }
+    if self.confirm_button == self.drag_button
+      || self
+        .reset_button
+        .is_some_and (|reset| reset == self.confirm_button || reset == self.drag_button)
+    {
+      bail! ("The confirm, drag, and reset buttons must be different");
+    }
+    if self.scale.is_some_and (|scale| scale <= 0.0) {
+      bail! ("The scale must be greater than 0");
+    }
+    Ok (self)
+  }
+
+  /// Makes the dimensions relative to the given monitor, see `--monitor`.
+  pub fn with_monitor (mut self, monitor: Option<String>) -> Self {
+    self.monitor = monitor;
+    self
+  }
+
+  pub fn with_work_area (mut self, work_area: bool) -> Self {
+    self.work_area = work_area;
+    self
+  }
+
+  pub fn with_margin (mut self, margin: u32) -> Self {
+    self.margin = margin;
+    self
+  }
+
+  pub fn with_gaps (mut self, gap: u32, outer_gap: u32) -> Self {
+    self.gap = gap;
+    self.outer_gap = outer_gap;
+    self
+  }
+
+  pub fn with_origin (mut self, origin: Origin) -> Self {
+    self.origin = origin;
+    self
+  }
+
+  /// Width to height ratio selections are constrained to.
+  pub fn with_aspect_lock (mut self, aspect_lock: Option<(u32, u32)>) -> Self {
+    self.aspect_lock = aspect_lock;
+    self
+  }
+
+  pub fn with_min_cells (mut self, columns: u32, rows: u32) -> Self {
+    self.min_cells = (columns, rows);
+    self
+  }
+
+  pub fn with_line_width (mut self, line_width: f64) -> Self {
+    self.line_width = line_width;
+    self
+  }
+
+  /// `None` detects the scale from the display.
+  pub fn with_scale (mut self, scale: Option<f64>) -> Self {
+    self.scale = scale;
+    self
+  }
+
+  /// Opacity of the dimming outside the selection, between 0.0 and 1.0.
+  pub fn with_dim (mut self, dim: f64) -> Self {
+    self.dim = dim;
+    self
+  }
+
+  /// Opacity of the whole overlay between 0.0 and 1.0.
+  pub fn with_opacity (mut self, opacity: Option<f64>) -> Self {
+    self.opacity = opacity;
+    self
+  }
+
+  /// The X button numbers for confirming, dragging, and resetting the
+  /// selection, they must be different.  `None` leaves resetting unbound.
+  pub fn with_buttons (mut self, confirm: u32, drag: u32, reset: Option<u32>) -> Self {
+    self.confirm_button = confirm;
+    self.drag_button = drag;
+    self.reset_button = reset;
+    self
+  }
+
+  pub fn with_click_select (mut self, click_select: bool) -> Self {
+    self.click_select = click_select;
+    self
+  }
+
+  pub fn with_hold_to_confirm (mut self, hold_to_confirm: bool) -> Self {
+    self.hold_to_confirm = hold_to_confirm;
+    self
+  }
+
+  pub fn with_live (mut self, live: bool) -> Self {
+    self.live = live;
+    self
+  }
+
+  /// Cancels the selection after this long without input.
+  pub fn with_timeout (mut self, timeout: Option<Duration>) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  pub fn with_flash (mut self, flash: bool) -> Self {
+    self.flash = flash;
+    self
+  }
+
+  pub fn with_client_walk (mut self, client_walk: bool) -> Self {
+    self.client_walk = client_walk;
+    self
+  }
+
+  pub fn with_snap_distance (mut self, snap_distance: u32) -> Self {
+    self.snap_distance = snap_distance;
+    self
+  }
+
+  /// Windows to ignore for snapping and tiling.
+  pub fn with_exclude (mut self, exclude: Vec<XWindow>) -> Self {
+    self.exclude = exclude;
+    self
+  }
+
+  pub fn with_labels (mut self, labels: bool) -> Self {
+    self.labels = labels;
+    self
+  }
+
+  pub fn with_repeat (mut self, repeat: bool) -> Self {
+    self.repeat = repeat;
+    self
+  }
+
+  pub fn with_dry_run (mut self, dry_run: bool) -> Self {
+    self.dry_run = dry_run;
+    self
+  }
+
+  pub fn with_verbose (mut self, verbose: bool) -> Self {
+    self.verbose = verbose;
+    self
+  }
+}
+
+impl TryFrom<&Args> for Config {
+  type Error = Box<dyn std::error::Error>;
+
+  fn try_from (args: &Args) -> Result<Self> {
+    for max in [&args.max_width, &args.max_height].into_iter ().flatten () {
+      if parse_dimension (max, 100)? <= 0 {
+        bail! (format! ("The maximum size must be greater than 0: `{}`", max));
+      }
+    }
+    // These are only optional for --restore
+    let (window, dimensions) = match (&args.window, &args.dimensions) {
+      (Some (window), Some (dimensions)) => (window, dimensions),
+      _ => bail! ("Missing arguments"),
+    };
+    let cells = match &args.cells {
+      Some (cells) => cells.clone (),
+      None => std::env::var ("GRID_RESIZE_CELLS")
+        .map_err (|_| "Missing the cells argument and GRID_RESIZE_CELLS is not set")?,
+    };
+    let (column_weights, row_weights, square_cells) = if args.square_cells {
+      match cells.parse::<u32> () {
+        Ok (count) if count > 0 => (Vec::new (), Vec::new (), Some (count)),
+        _ => bail! ("Invalid grid size, should be a single number with --square-cells"),
+      }
+    } else {
+      let cells: Vec<&str> = cells.split (',').collect ();
+      if cells.len () != 2 {
+        bail! ("Invalid grid size, should be: `vertical,horizontal`");
+      }
+      (parse_weights (cells[0])?, parse_weights (cells[1])?, None)
+    };
+    let method = MoveResizeMethod::from_str (&args.method)?
+      .with_gravity (parse_gravity (&args.gravity)?)
+      .with_source (parse_source_indication (&args.source_indication)?);
+    let color = match args.color {
+      Some (color) => color,
+      None => match std::env::var ("GRID_RESIZE_COLOR") {
+        Ok (color) => color
+          .parse ()
+          .map_err (|error| format! ("Invalid GRID_RESIZE_COLOR: {}", error))?,
+        Err (_) => RGB::new (0.898, 0.513, 0.964),
+      },
+    };
+    Ok (Self {
+      window: window.clone (),
+      client_walk: !args.no_client_walk,
+      dimensions: dimensions.clone (),
+      monitor: args.monitor.clone (),
+      work_area: args.work_area,
+      margin: args.margin,
+      column_weights,
+      row_weights,
+      square_cells,
+      grid_from_window: args.grid_from_window.as_deref ().map (parse_window_id).transpose ()?,
+      initial_selection: args
+        .initial_selection
+        .as_deref ()
+        .map (parse_initial_selection)
+        .transpose ()?,
+      reserve_space: args.reserve_space.as_deref ().map (Edge::from_str).transpose ()?,
+      activate: args.activate,
+      keep_open: args.keep_open,
+      double_buffer: !args.no_double_buffer,
+      gap: args.gap,
+      outer_gap: args.outer_gap,
+      origin: Origin::from_str (&args.origin)?,
+      aspect_lock: parse_aspect_ratio (&args.aspect_lock)?,
+      min_cells: parse_min_cells (&args.min_cells)?,
+      grid_style: GridStyle::from_str (&args.grid_style)?,
+      method,
+      format: OutputFormat::from_str (&args.format)?,
+      line_color: args.line_color.unwrap_or (color),
+      selection_color: args.selection_color.unwrap_or (color),
+      hover_color: args
+        .hover_color
+        .unwrap_or_else (|| RGB::lerp (&color, RGB::new (0.9, 0.9, 0.9), 0.6)),
+      line_width: args.line_width,
+      scale: args.scale,
+      dim: args.dim,
+      confirm_button: args.confirm_button,
+      drag_button: args.drag_button,
+      // The middle button resets by default, unless it is already used for
+      // confirming or dragging.
+      reset_button: args.reset_button.or (
+        (Button2 != args.confirm_button && Button2 != args.drag_button).then_some (Button2),
+      ),
+      right_button_pressed: args.right_button_pressed,
+      click_select: args.click_select,
+      hold_to_confirm: args.hold_to_confirm,
+      quit_on_focus_loss: args.quit_on_focus_loss,
+      cursor: cursor_shape (&args.cursor)?,
+      window_class: args.window_class.clone (),
+      window_instance: args.window_instance.clone (),
+      opacity: args.opacity,
+      live: args.live,
+      motion_interval: (args.motion_hz != 0).then (|| Duration::from_secs (1) / args.motion_hz),
+      timeout: (args.timeout != 0).then (|| Duration::from_millis (args.timeout)),
+      respect_hints: args.respect_hints,
+      center_in_cell: args.center_in_cell,
+      flash: !args.no_flash,
+      animate_duration: (args.animate_duration != 0)
+        .then (|| Duration::from_millis (args.animate_duration)),
+      max_width: args.max_width.clone (),
+      max_height: args.max_height.clone (),
+      dry_run: args.dry_run,
+      snap_distance: args.snap_distance,
+      exclude: args
+        .exclude_windows
+        .iter ()
+        .map (|id| parse_window_id (id))
+        .collect::<Result<_>> ()?,
+      unfullscreen: args.unfullscreen,
+      unmaximize: args
+        .unmaximize
+        .unwrap_or (!matches! (method, MoveResizeMethod::Direct)),
+      verify: args.verify,
+      repeat: args.repeat,
+      labels: args.labels,
+      key_accel: args.key_accel,
+      verbose: args.verbose,
+      swap_with: args.swap_with.clone (),
+    })
+    .and_then (Config::validate)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse (args: &[&str]) -> Result<Config> {
+    let args = Args::try_parse_from (["grid-resize", "0x1", "0,0,100%,100%"].iter ().chain (args))?;
+    Config::try_from (&args)
+  }
+
+  #[test]
+  fn valid_arguments () {
+    let config = parse (&["2:1,3"]).unwrap ();
+    assert_eq! (config.column_weights, [2, 1]);
+    assert_eq! (config.row_weights, [1, 1, 1]);
+    assert_eq! (config.square_cells, None);
+    let config = parse (&["4", "--square-cells"]).unwrap ();
+    assert_eq! (config.square_cells, Some (4));
+    assert! (config.column_weights.is_empty ());
+  }
+
+  #[test]
+  fn same_confirm_and_drag_button () {
+    assert! (parse (&["2,2", "--confirm-button", "3"]).is_err ());
+    assert! (parse (&["2,2", "--confirm-button", "2", "--drag-button", "2"]).is_err ());
+    assert! (parse (&["2,2", "--reset-button", "1"]).is_err ());
+  }
+
+  #[test]
+  fn reset_button_default () {
+    assert_eq! (parse (&["2,2"]).unwrap ().reset_button, Some (Button2));
+    // The default is left unbound if the middle button is used otherwise.
+    assert_eq! (parse (&["2,2", "--drag-button", "2"]).unwrap ().reset_button, None);
+    assert_eq! (
+      parse (&["2,2", "--confirm-button", "2", "--reset-button", "1"])
+        .unwrap ()
+        .reset_button,
+      Some (1)
+    );
+  }
+
+  #[test]
+  fn dim_out_of_range () {
+    assert! (parse (&["2,2", "--dim", "1.5"]).is_err ());
+    assert! (parse (&["2,2", "--dim", "-0.1"]).is_err ());
+    assert! (parse (&["2,2", "--dim", "1.0"]).is_ok ());
+  }
+
+  #[test]
+  fn bad_cells () {
+    assert! (parse (&["2"]).is_err ());
+    assert! (parse (&["2,2,2"]).is_err ());
+    assert! (parse (&["a,2"]).is_err ());
+    assert! (parse (&["0,2"]).is_err ());
+    assert! (parse (&["2,2", "--square-cells"]).is_err ());
+    assert! (parse (&["18446744073709551615,2"]).is_err ());
+    assert! (parse (&["4294967295:1,2"]).is_err ());
+  }
+
+  #[test]
+  fn square_cells_zero () {
+    assert! (parse (&["0", "--square-cells"]).is_err ());
+  }
+
+  #[test]
+  fn builder () {
+    let config = Config::new (":ACTIVE:", "0,0,100%,100%", "3,2")
+      .unwrap ()
+      .with_gaps (4, 8)
+      .with_dim (0.5)
+      .with_buttons (1, 2, Some (3))
+      .validate ()
+      .unwrap ();
+    assert_eq! ((config.gap, config.outer_gap), (4, 8));
+    assert_eq! (config.column_weights, [1, 1, 1]);
+    assert! (Config::new (":ACTIVE:", "0,0,100%,100%", "3,2")
+      .unwrap ()
+      .with_dim (2.0)
+      .validate ()
+      .is_err ());
+    assert! (Config::new (":ACTIVE:", "0,0,100%,100%", "3,2")
+      .unwrap ()
+      .with_buttons (1, 1, Some (2))
+      .validate ()
+      .is_err ());
+  }
+
+  // This is the only test that uses the variable so it does not race with
+  // the others.
+  #[test]
+  fn cells_from_environment () {
+    std::env::set_var ("GRID_RESIZE_CELLS", "3,2");
+    let config = parse (&[]).unwrap ();
+    assert_eq! (config.column_weights, [1, 1, 1]);
+    assert_eq! (config.row_weights, [1, 1]);
+    // The argument takes precedence
+    assert_eq! (parse (&["4,4"]).unwrap ().column_weights, [1; 4]);
+    std::env::set_var ("GRID_RESIZE_CELLS", "3");
+    assert! (parse (&[]).is_err ());
+    std::env::remove_var ("GRID_RESIZE_CELLS");
+    assert! (parse (&[]).is_err ());
+  }
+}
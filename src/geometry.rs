@@ -0,0 +1,587 @@
+use super::Result;
+use std::str::FromStr;
+
+#[derive(Copy, Clone)]
+pub enum Origin {
+  TopLeft,
+  TopRight,
+  BottomLeft,
+  BottomRight,
+}
+
+impl FromStr for Origin {
+  type Err = Box<dyn std::error::Error>;
+
+  fn from_str (s: &str) -> Result<Self> {
+    match s.to_lowercase ().as_str () {
+      "top-left" => Ok (Self::TopLeft),
+      "top-right" => Ok (Self::TopRight),
+      "bottom-left" => Ok (Self::BottomLeft),
+      "bottom-right" => Ok (Self::BottomRight),
+      _ => bail! ("Invalid origin"),
+    }
+  }
+}
+
+impl Origin {
+  /// Returns whether the horizontal and vertical indices are reversed.
+  pub fn flips (&self) -> (bool, bool) {
+    match self {
+      Self::TopLeft => (false, false),
+      Self::TopRight => (true, false),
+      Self::BottomLeft => (false, true),
+      Self::BottomRight => (true, true),
+    }
+  }
+}
+
+/// Cell and grid line indices are counted from the grid's `origin` corner.
+///
+/// Pixel positions are relative to the top-left corner of the grid, so they
+/// stay unsigned wherever the grid is on the screen, and callers add the
+/// signed position of the grid to get absolute coordinates.
+pub struct Grid {
+  pub vertical_cells: u32,
+  pub horizontal_cells: u32,
+  column_weights: Vec<u32>,
+  row_weights: Vec<u32>,
+  /// Pixel positions of the vertical grid lines, from left to right.
+  pub columns: Vec<u32>,
+  /// Pixel positions of the horizontal grid lines, from top to bottom.
+  pub rows: Vec<u32>,
+  gap: u32,
+  outer_gap: u32,
+  pub origin: Origin,
+  /// Width to height ratio selections are constrained to.
+  aspect_lock: Option<(u32, u32)>,
+  /// Minimum number of columns and rows a selection spans.
+  min_cells: (u32, u32),
+}
+
+impl Grid {
+  /// Creates a grid with cell sizes proportional to the given weights.  The
+  /// size should be a multiple of the sum of weights for the lines to be
+  /// exact.
+  pub fn new (width: u32, height: u32, column_weights: Vec<u32>, row_weights: Vec<u32>) -> Self {
+    Self {
+      vertical_cells: column_weights.len () as u32,
+      horizontal_cells: row_weights.len () as u32,
+      columns: Self::lines (width, &column_weights),
+      rows: Self::lines (height, &row_weights),
+      column_weights,
+      row_weights,
+      gap: 0,
+      outer_gap: 0,
+      origin: Origin::TopLeft,
+      aspect_lock: None,
+      min_cells: (1, 1),
+    }
+  }
+
+  /// Returns the positions of the lines splitting `size` according to the
+  /// given weights, including both ends.
+  fn lines (size: u32, weights: &[u32]) -> Vec<u32> {
+    let total: u64 = weights.iter ().map (|&weight| weight as u64).sum ();
+    let mut sum = 0;
+    let mut lines = vec![0];
+    for &weight in weights {
+      sum += weight as u64;
+      lines.push ((size as u64 * sum / total) as u32);
+    }
+    lines
+  }
+
+  /// Recomputes the grid lines for a new size.
+  pub fn resize (&mut self, width: u32, height: u32) {
+    self.columns = Self::lines (width, &self.column_weights);
+    self.rows = Self::lines (height, &self.row_weights);
+  }
+
+  /// Replaces the column and row weights, which also changes the number of
+  /// cells.  The grid keeps its size.
+  pub fn set_weights (&mut self, column_weights: Vec<u32>, row_weights: Vec<u32>) {
+    let width = self.columns.last ().copied ().unwrap_or (0);
+    let height = self.rows.last ().copied ().unwrap_or (0);
+    self.vertical_cells = column_weights.len () as u32;
+    self.horizontal_cells = row_weights.len () as u32;
+    self.column_weights = column_weights;
+    self.row_weights = row_weights;
+    self.resize (width, height);
+  }
+
+  /// Returns the sum of the column and row weights.
+  pub fn total_weights (&self) -> (u32, u32) {
+    (self.column_weights.iter ().sum (), self.row_weights.iter ().sum ())
+  }
+
+  pub fn with_gaps (mut self, gap: u32, outer_gap: u32) -> Self {
+    self.gap = gap;
+    self.outer_gap = outer_gap;
+    self
+  }
+
+  pub fn with_origin (mut self, origin: Origin) -> Self {
+    self.origin = origin;
+    self
+  }
+
+  pub fn with_aspect_lock (mut self, aspect_lock: Option<(u32, u32)>) -> Self {
+    self.aspect_lock = aspect_lock;
+    self
+  }
+
+  pub fn with_min_cells (mut self, min_cells: (u32, u32)) -> Self {
+    self.min_cells = min_cells;
+    self
+  }
+
+  /// Converts grid line indices between being counted from the origin and
+  /// being counted from the top-left corner.
+  fn flip (&self, index: (u32, u32)) -> (u32, u32) {
+    let (flip_x, flip_y) = self.origin.flips ();
+    (
+      if flip_x {
+        self.vertical_cells - index.0
+      } else {
+        index.0
+      },
+      if flip_y {
+        self.horizontal_cells - index.1
+      } else {
+        index.1
+      },
+    )
+  }
+
+  /// Returns the cell containing the given point, counted from the top-left
+  /// corner.  A point on a grid line belongs to the cell after it, points
+  /// outside the grid belong to the closest cell on its edge.
+  pub fn cell_at (&self, x: i32, y: i32) -> (u32, u32) {
+    let index = |lines: &[u32], cells: u32, position: i32| {
+      // Negative positions would wrap around to the end of the grid.
+      let position = position.max (0) as u32;
+      let lines_before = lines.partition_point (|&line| line <= position) as u32;
+      lines_before.saturating_sub (1).min (cells - 1)
+    };
+    (
+      index (&self.columns, self.vertical_cells, x),
+      index (&self.rows, self.horizontal_cells, y),
+    )
+  }
+
+  /// Returns the corner of the cell containing the given point that is
+  /// closest to the origin.  This is also the index of the cell.
+  pub fn lower_bound (&self, x: i32, y: i32) -> (u32, u32) {
+    let (flip_x, flip_y) = self.origin.flips ();
+    let (column, row) = self.cell_at (x, y);
+    (
+      if flip_x {
+        self.vertical_cells - 1 - column
+      } else {
+        column
+      },
+      if flip_y {
+        self.horizontal_cells - 1 - row
+      } else {
+        row
+      },
+    )
+  }
+
+  /// Returns the corner of the cell containing the given point that is
+  /// farthest from the origin.
+  fn upper_bound (&self, x: i32, y: i32) -> (u32, u32) {
+    let (x, y) = self.lower_bound (x, y);
+    (x + 1, y + 1)
+  }
+
+  /// Returns the pixel position of the given grid line indices.
+  fn position (&self, index: (u32, u32)) -> (u32, u32) {
+    let (x, y) = self.flip (index);
+    (self.columns[x as usize], self.rows[y as usize])
+  }
+
+  /// Returns the pixel position of the center of the given cell.
+  pub fn cell_center (&self, cell: (u32, u32)) -> (i32, i32) {
+    let (x1, y1) = self.position (cell);
+    let (x2, y2) = self.position ((cell.0 + 1, cell.1 + 1));
+    (((x1 + x2) / 2) as i32, ((y1 + y2) / 2) as i32)
+  }
+
+  /// Returns the spreadsheet-style label of the given cell, columns are
+  /// lettered from `A` and rows numbered from `1`.
+  pub fn cell_label (&self, cell: (u32, u32)) -> String {
+    let mut column = String::new ();
+    let mut index = cell.0 + 1;
+    while index > 0 {
+      index -= 1;
+      column.insert (0, (b'A' + (index % 26) as u8) as char);
+      index /= 26;
+    }
+    format! ("{}{}", column, cell.1 + 1)
+  }
+
+  /// Returns the cell with the given label, or `None` if there is no such
+  /// cell in the grid.
+  pub fn parse_label (&self, label: &str) -> Option<(u32, u32)> {
+    let split = label.find (|c: char| c.is_ascii_digit ())?;
+    let (column, row) = label.split_at (split);
+    if column.is_empty () || !column.bytes ().all (|c| c.is_ascii_uppercase ()) {
+      return None;
+    }
+    let column = column
+      .bytes ()
+      .try_fold (0u32, |acc, c| acc.checked_mul (26)?.checked_add ((c - b'A') as u32 + 1))?;
+    let row: u32 = row.parse ().ok ()?;
+    (column >= 1
+      && column <= self.vertical_cells
+      && row >= 1
+      && row <= self.horizontal_cells)
+      .then_some ((column - 1, row - 1))
+  }
+
+  /// Returns the space to leave free on both sides of a grid line along an
+  /// axis with `cells` cells.  Gaps between cells are split between the two
+  /// neighbors so their sum is exactly `gap`.
+  fn gaps_at (&self, index: u32, cells: u32) -> (u32, u32) {
+    if index == 0 || index == cells {
+      (self.outer_gap, self.outer_gap)
+    } else {
+      (self.gap / 2, self.gap - self.gap / 2)
+    }
+  }
+
+  /// Returns the rectangle spanned by the given opposite corner indices, with
+  /// the gaps removed.
+  pub fn rect (&self, p1: (u32, u32), p2: (u32, u32)) -> (i32, i32, u32, u32) {
+    // Sort the corners from the top-left so the gaps end up on the right side.
+    let (p1, p2) = (self.flip (p1), self.flip (p2));
+    let (x1, x2) = (p1.0.min (p2.0), p1.0.max (p2.0));
+    let (y1, y2) = (p1.1.min (p2.1), p1.1.max (p2.1));
+    let left = self.gaps_at (x1, self.vertical_cells).1;
+    let top = self.gaps_at (y1, self.horizontal_cells).1;
+    let right = self.gaps_at (x2, self.vertical_cells).0;
+    let bottom = self.gaps_at (y2, self.horizontal_cells).0;
+    let (x1, x2) = (self.columns[x1 as usize], self.columns[x2 as usize]);
+    let (y1, y2) = (self.rows[y1 as usize], self.rows[y2 as usize]);
+    (
+      (x1 + left) as i32,
+      (y1 + top) as i32,
+      (x2 - x1).saturating_sub (left + right),
+      (y2 - y1).saturating_sub (top + bottom),
+    )
+  }
+
+  /// Shrinks the longer axis of the span between `lower` and `upper` until its
+  /// rectangle best matches the `ratio`.  Cells are removed from the side
+  /// opposite of `anchor` so the cell it is in stays selected.
+  fn lock_aspect (
+    &self,
+    lower: (u32, u32),
+    upper: (u32, u32),
+    anchor: (u32, u32),
+    ratio: (u32, u32),
+  ) -> ((u32, u32), (u32, u32)) {
+    let target = ratio.0 as f64 / ratio.1 as f64;
+    let error = |(lower, upper)| {
+      let (_, _, width, height) = self.rect (lower, upper);
+      (width.max (1) as f64 / height.max (1) as f64 / target).ln ().abs ()
+    };
+    let (_, _, width, height) = self.rect (lower, upper);
+    let too_wide = width as f64 / height.max (1) as f64 > target;
+    let mut best = (lower, upper);
+    loop {
+      let (lower, upper) = best;
+      let next = if too_wide {
+        if upper.0 - lower.0 <= 1 {
+          break;
+        } else if anchor.0 == lower.0 {
+          (lower, (upper.0 - 1, upper.1))
+        } else {
+          ((lower.0 + 1, lower.1), upper)
+        }
+      } else if upper.1 - lower.1 <= 1 {
+        break;
+      } else if anchor.1 == lower.1 {
+        (lower, (upper.0, upper.1 - 1))
+      } else {
+        ((lower.0, lower.1 + 1), upper)
+      };
+      if error (next) >= error (best) {
+        break;
+      }
+      best = next;
+    }
+    best
+  }
+
+  /// Grows the span between `lower` and `upper` to at least `min_cells`
+  /// cells.  Cells are added on the side opposite of `anchor` unless that
+  /// side is already at the edge of the grid.
+  fn expand_to_min (
+    &self,
+    lower: (u32, u32),
+    upper: (u32, u32),
+    anchor: (u32, u32),
+  ) -> ((u32, u32), (u32, u32)) {
+    let expand = |mut lower: u32, mut upper: u32, anchor: u32, min: u32, cells: u32| {
+      while upper - lower < min.min (cells) {
+        if (anchor == lower && upper < cells) || lower == 0 {
+          upper += 1;
+        } else {
+          lower -= 1;
+        }
+      }
+      (lower, upper)
+    };
+    let (x1, x2) = expand (lower.0, upper.0, anchor.0, self.min_cells.0, self.vertical_cells);
+    let (y1, y2) = expand (lower.1, upper.1, anchor.1, self.min_cells.1, self.horizontal_cells);
+    ((x1, y1), (x2, y2))
+  }
+}
+
+pub struct Selection {
+  pub p1_x: i32,
+  pub p1_y: i32,
+  pub p2_x: i32,
+  pub p2_y: i32,
+}
+
+impl Selection {
+  pub fn new (x: i32, y: i32) -> Self {
+    Self {
+      p1_x: x,
+      p1_y: y,
+      p2_x: x,
+      p2_y: y,
+    }
+  }
+
+  /// Returns a selection spanning the cells from `first` to `last`, or
+  /// `None` if either is outside the grid.
+  pub fn from_cells (grid: &Grid, first: (u32, u32), last: (u32, u32)) -> Option<Self> {
+    if first.0.max (last.0) >= grid.vertical_cells || first.1.max (last.1) >= grid.horizontal_cells
+    {
+      return None;
+    }
+    let (p1_x, p1_y) = grid.cell_center (first);
+    let (p2_x, p2_y) = grid.cell_center (last);
+    Some (Self {
+      p1_x,
+      p1_y,
+      p2_x,
+      p2_y,
+    })
+  }
+
+  /// Returns the corners of the selection closest to and farthest from the
+  /// grid origin.
+  pub fn get (&self, grid: &Grid) -> ((u32, u32), (u32, u32)) {
+    let lower_1 = grid.lower_bound (self.p1_x, self.p1_y);
+    let lower_2 = grid.lower_bound (self.p2_x, self.p2_y);
+    let upper_1 = grid.upper_bound (self.p1_x, self.p1_y);
+    let upper_2 = grid.upper_bound (self.p2_x, self.p2_y);
+    let lower = (lower_1.0.min (lower_2.0), lower_1.1.min (lower_2.1));
+    let upper = (upper_1.0.max (upper_2.0), upper_1.1.max (upper_2.1));
+    let (lower, upper) = match grid.aspect_lock {
+      Some (ratio) => grid.lock_aspect (lower, upper, lower_1, ratio),
+      None => (lower, upper),
+    };
+    grid.expand_to_min (lower, upper, lower_1)
+  }
+
+  pub fn get_dimensions (&self, grid: &Grid) -> (i32, i32, u32, u32) {
+    let (p1, p2) = self.get (grid);
+    grid.rect (p1, p2)
+  }
+
+  /// Moves the second point by the given number of cells, snapping it to the
+  /// center of the resulting cell.  If `extend` is false the first point is
+  /// moved along with it, otherwise it stays in place as the anchor.
+  pub fn move_cursor (&mut self, grid: &Grid, dx: i32, dy: i32, extend: bool) {
+    // The directions are on screen, not relative to the origin
+    let (flip_x, flip_y) = grid.origin.flips ();
+    let dx = if flip_x { -dx } else { dx };
+    let dy = if flip_y { -dy } else { dy };
+    let (x, y) = grid.lower_bound (self.p2_x, self.p2_y);
+    let x = (x as i32 + dx).clamp (0, grid.vertical_cells as i32 - 1) as u32;
+    let y = (y as i32 + dy).clamp (0, grid.horizontal_cells as i32 - 1) as u32;
+    self.move_to (grid, (x, y), extend);
+  }
+
+  /// Moves the second point to the center of the given cell, and the first
+  /// one along with it unless `extend` is true.
+  pub fn move_to (&mut self, grid: &Grid, cell: (u32, u32), extend: bool) {
+    (self.p2_x, self.p2_y) = grid.cell_center (cell);
+    if !extend {
+      self.p1_x = self.p2_x;
+      self.p1_y = self.p2_y;
+    }
+  }
+}
+
+/// Correct the given dimensions to account for integer division in the grid
+/// logic.  The units are the sums of the column and row weights.  The result
+/// is centered in the given area; if the unused space is odd, the extra pixel
+/// is left on the right or bottom so the result never exceeds the area.
+pub fn correct_dimensions (
+  x: i32,
+  y: i32,
+  width: u32,
+  height: u32,
+  vertical_units: u32,
+  horizontal_units: u32,
+) -> Result<(i32, i32, u32, u32)> {
+  if vertical_units > width || horizontal_units > height {
+    bail! ("Invalid grid size, the cells would be smaller than a pixel");
+  }
+  let unit_width = width / vertical_units;
+  let unit_height = height / horizontal_units;
+  let use_width = unit_width * vertical_units;
+  let use_height = unit_height * horizontal_units;
+  let use_x = x + ((width - use_width) / 2) as i32;
+  let use_y = y + ((height - use_height) / 2) as i32;
+  Ok ((use_x, use_y, use_width, use_height))
+}
+
+/// Returns the largest grid of square cells of the size that fits `count`
+/// cells along the shorter side of the given rectangle, centered in it, and
+/// the number of cells along its width and height.
+#[allow(clippy::type_complexity)]
+pub fn square_grid (
+  rect: (i32, i32, u32, u32),
+  count: u32,
+) -> Result<((i32, i32, u32, u32), (u32, u32))> {
+  let (x, y, width, height) = rect;
+  let size = width.min (height) / count;
+  if size == 0 {
+    bail! ("Invalid grid size, the cells would be smaller than a pixel");
+  }
+  let (columns, rows) = (width / size, height / size);
+  let use_width = columns * size;
+  let use_height = rows * size;
+  let use_x = x + ((width - use_width) / 2) as i32;
+  let use_y = y + ((height - use_height) / 2) as i32;
+  Ok (((use_x, use_y, use_width, use_height), (columns, rows)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// 3x2 cells of 100x100 pixels.
+  fn grid () -> Grid {
+    Grid::new (300, 200, vec![1; 3], vec![1; 2])
+  }
+
+  #[test]
+  fn selection_with_reversed_anchors () {
+    let grid = grid ();
+    let mut selection = Selection::new (250, 150);
+    (selection.p2_x, selection.p2_y) = (50, 50);
+    assert_eq! (selection.get (&grid), ((0, 0), (3, 2)));
+    assert_eq! (selection.get_dimensions (&grid), (0, 0, 300, 200));
+    // Only reversed along one axis
+    let mut selection = Selection::new (50, 150);
+    (selection.p2_x, selection.p2_y) = (150, 50);
+    assert_eq! (selection.get (&grid), ((0, 0), (2, 2)));
+  }
+
+  #[test]
+  fn correct_dimensions_with_remainder () {
+    // 1003 = 4 * 250 + 3, the odd pixel is left on the right.
+    assert_eq! (correct_dimensions (0, 0, 1003, 800, 4, 4).unwrap (), (1, 0, 1000, 800));
+    assert! (correct_dimensions (0, 0, 3, 800, 4, 4).is_err ());
+  }
+
+  #[test]
+  fn correct_dimensions_centers_the_result () {
+    // 1000 = 3 * 333 + 1, a single unused pixel stays on the right.
+    assert_eq! (correct_dimensions (0, 0, 1000, 1000, 3, 3).unwrap (), (0, 0, 999, 999));
+    // 1001 = 3 * 333 + 2 is split evenly.
+    assert_eq! (correct_dimensions (0, 0, 1001, 1001, 3, 3).unwrap (), (1, 1, 999, 999));
+    // 1000 = 7 * 142 + 6
+    assert_eq! (correct_dimensions (0, 0, 1000, 1000, 7, 7).unwrap (), (3, 3, 994, 994));
+    // Offsets are kept
+    assert_eq! (
+      correct_dimensions (100, 50, 1001, 1000, 3, 7).unwrap (),
+      (101, 53, 999, 994)
+    );
+    // Exact sizes are unchanged
+    assert_eq! (correct_dimensions (10, 20, 900, 600, 3, 2).unwrap (), (10, 20, 900, 600));
+  }
+
+  #[test]
+  fn cell_at () {
+    let grid = grid ();
+    assert_eq! (grid.cell_at (0, 0), (0, 0));
+    // On an inner line
+    assert_eq! (grid.cell_at (100, 100), (1, 1));
+    assert_eq! (grid.cell_at (99, 99), (0, 0));
+    // Last pixel
+    assert_eq! (grid.cell_at (299, 199), (2, 1));
+    // Past the width and height
+    assert_eq! (grid.cell_at (300, 200), (2, 1));
+    assert_eq! (grid.cell_at (300, 50), (2, 0));
+  }
+
+  #[test]
+  fn cell_at_clamps_points_outside () {
+    let grid = grid ();
+    assert_eq! (grid.cell_at (-10, -10), (0, 0));
+    assert_eq! (grid.cell_at (-10, 150), (0, 1));
+    assert_eq! (grid.cell_at (i32::MIN, i32::MIN), (0, 0));
+    // Beyond the bottom-right corner
+    assert_eq! (grid.cell_at (310, 210), (2, 1));
+    assert_eq! (grid.cell_at (i32::MAX, i32::MAX), (2, 1));
+    assert_eq! (grid.lower_bound (-10, -10), (0, 0));
+    assert_eq! (grid.lower_bound (310, 210), (2, 1));
+  }
+
+  #[test]
+  fn grid_at_negative_origin () {
+    // A monitor left of the primary one
+    let (x, y, width, height) = correct_dimensions (-1920, -100, 1921, 1080, 3, 2).unwrap ();
+    assert_eq! ((x, y, width, height), (-1920, -100, 1920, 1080));
+    let grid = Grid::new (width, height, vec![1; 3], vec![1; 2]);
+    // Root coordinates are made relative to the grid before looking them up.
+    let cell = |root_x: i32, root_y: i32| grid.cell_at (root_x - x, root_y - y);
+    assert_eq! (cell (-1920, -100), (0, 0));
+    assert_eq! (cell (-1000, 500), (1, 1));
+    assert_eq! (cell (-1, 979), (2, 1));
+    // Outside the grid on either side
+    assert_eq! (cell (-1921, -101), (0, 0));
+    assert_eq! (cell (0, 980), (2, 1));
+    let (rect_x, rect_y, rect_width, rect_height) = grid.rect ((1, 1), (2, 2));
+    assert_eq! (
+      (x + rect_x, y + rect_y, rect_width, rect_height),
+      (-1280, 440, 640, 540)
+    );
+    let (rect, cells) = square_grid ((-1920, -100, 1920, 1080), 2).unwrap ();
+    assert_eq! ((rect, cells), ((-1770, -100, 1620, 1080), (3, 2)));
+  }
+
+  #[test]
+  fn lines_with_large_weights () {
+    let grid = Grid::new (60000, 100, vec![u32::MAX / 2, u32::MAX / 2], vec![1]);
+    assert_eq! (grid.columns, [0, 30000, 60000]);
+  }
+
+  #[test]
+  fn lower_bound_maps_points_to_cells () {
+    let grid = grid ();
+    // Inside cells
+    assert_eq! (grid.lower_bound (50, 50), (0, 0));
+    assert_eq! (grid.lower_bound (150, 150), (1, 1));
+    // On lines
+    assert_eq! (grid.lower_bound (100, 100), (1, 1));
+    assert_eq! (grid.lower_bound (200, 50), (2, 0));
+    // At the edges
+    assert_eq! (grid.lower_bound (0, 0), (0, 0));
+    assert_eq! (grid.lower_bound (299, 199), (2, 1));
+    // Counted from a different origin
+    let grid = grid.with_origin (Origin::BottomRight);
+    assert_eq! (grid.lower_bound (0, 0), (2, 1));
+    assert_eq! (grid.lower_bound (299, 199), (0, 0));
+  }
+}
@@ -0,0 +1,52 @@
+use super::Result;
+use std::path::PathBuf;
+
+/// A named layout from the layouts file, the values use the same format as
+/// the `cells` argument and `--preset`.
+pub struct Layout {
+  pub cells: String,
+  pub preset: String,
+}
+
+/// Returns the path of the layouts file,
+/// `$XDG_CONFIG_HOME/grid-resize/layouts.toml` or
+/// `~/.config/grid-resize/layouts.toml`.
+fn layouts_file () -> Option<PathBuf> {
+  let config = std::env::var_os ("XDG_CONFIG_HOME")
+    .map (PathBuf::from)
+    .or_else (|| std::env::var_os ("HOME").map (|home| PathBuf::from (home).join (".config")))?;
+  Some (config.join ("grid-resize").join ("layouts.toml"))
+}
+
+/// Loads the layout with the given name.  Each layout is a table in the file:
+///
+/// ```toml
+/// [browser-left]
+/// cells = "2,1"
+/// preset = "0,0:0,0"
+/// ```
+pub fn load (name: &str) -> Result<Layout> {
+  let path = layouts_file ().ok_or ("Could not determine the config directory")?;
+  let content = match std::fs::read_to_string (&path) {
+    Ok (content) => content,
+    Err (error) => bail! (format! ("Failed to read {}: {}", path.display (), error)),
+  };
+  let layouts: toml::Value = match toml::from_str (&content) {
+    Ok (layouts) => layouts,
+    Err (error) => bail! (format! ("Invalid layouts file {}: {}", path.display (), error)),
+  };
+  let layout = match layouts.get (name) {
+    Some (layout) => layout,
+    None => bail! (format! ("No layout named `{}` in {}", name, path.display ())),
+  };
+  let get = |key: &str| -> Result<String> {
+    match layout.get (key).and_then (|value| value.as_str ()) {
+      Some (value) => Ok (value.to_string ()),
+      None => bail! (format! ("Layout `{}` is missing the `{}` string", name, key)),
+    }
+  };
+  Ok (Layout {
+    cells: get ("cells")?,
+    preset: get ("preset")?,
+  })
+}
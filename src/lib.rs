@@ -0,0 +1,3087 @@
+//! The grid overlay of the `grid-resize` program.
+//!
+//! [`run`] does everything the command-line program does, other programs can
+//! drive the grid for a window with [`GridResize`].  Cell and selection
+//! geometry lives in the [`geometry`] module.
+#[macro_use]
+extern crate simple_error;
+
+use std::{
+  cell::Cell,
+  ffi::{c_char, c_uchar, c_uint, CStr},
+  path::PathBuf,
+  str::FromStr,
+  sync::atomic::{AtomicBool, Ordering},
+  time::{Duration, Instant},
+};
+
+use cairo::{
+  Context, FontSlant, FontWeight, Format, ImageSurface, Operator, RadialGradient, Surface,
+};
+use cairo_sys::{cairo_xlib_surface_create, cairo_xlib_surface_set_size};
+use clap::Parser;
+use x11::xlib::*;
+
+mod buttons;
+mod config;
+pub mod geometry;
+mod layouts;
+mod x;
+use buttons::{ButtonAction, Buttons};
+pub use config::Config;
+use geometry::{correct_dimensions, square_grid, Grid, Origin, Selection};
+pub use x::Display;
+use x::{
+  display::{Monitor, ScopedKeyboardGrab, ScopedPointerGrab},
+  Window, XDisplay, XWindow, NONE, XC_CROSSHAIR,
+};
+
+type StdResult<T, E> = std::result::Result<T, E>;
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Whether to log what is done, set by `--verbose`.
+static VERBOSE: AtomicBool = AtomicBool::new (false);
+
+/// Prints a message to stderr if `--verbose` was given.
+macro_rules! verbose {
+  ($($arg:tt)*) => {
+    if VERBOSE.load (Ordering::Relaxed) {
+      eprintln! ("grid-resize: {}", format_args! ($($arg)*));
+    }
+  };
+}
+
+#[derive(Copy, Clone, Debug)]
+enum MoveResizeMethod {
+  Direct,
+  Configure,
+  Message { gravity: i32, source: i64 },
+  /// Tries `Configure`, `Message`, and `Direct` in that order until the
+  /// target window changes.
+  Auto { gravity: i32, source: i64 },
+}
+
+impl MoveResizeMethod {
+  fn from_str (s: &str) -> Result<Self> {
+    match s.to_lowercase ().as_str () {
+      "direct" => Ok (Self::Direct),
+      "configure" => Ok (Self::Configure),
+      "message" => Ok (Self::Message {
+        gravity: NorthWestGravity,
+        source: 2,
+      }),
+      "auto" => Ok (Self::Auto {
+        gravity: NorthWestGravity,
+        source: 2,
+      }),
+      _ => bail! ("Invalid method"),
+    }
+  }
+
+  /// Sets the gravity used by the `Message` method, other methods ignore it.
+  fn with_gravity (self, gravity: i32) -> Self {
+    match self {
+      Self::Message { source, .. } => Self::Message { gravity, source },
+      Self::Auto { source, .. } => Self::Auto { gravity, source },
+      method => method,
+    }
+  }
+
+  /// Sets the source indication used by the `Message` method, other methods
+  /// ignore it.
+  fn with_source (self, source: i64) -> Self {
+    match self {
+      Self::Message { gravity, .. } => Self::Message { gravity, source },
+      Self::Auto { gravity, .. } => Self::Auto { gravity, source },
+      method => method,
+    }
+  }
+
+  /// Moves and resizes the target.  `Auto` is replaced by the method that
+  /// worked, so the probing only happens once.
+  fn apply (&mut self, display: &Display, target: &Window, geometry: (i32, i32, u32, u32)) {
+    let (x, y, w, h) = geometry;
+    match *self {
+      Self::Direct => {
+        target.move_and_resize (x, y, w, h);
+      }
+      Self::Message { gravity, source } => {
+        // https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html#idm46463187598320
+        let event = XEvent {
+          client_message: XClientMessageEvent {
+            type_: ClientMessage,
+            serial: 0,        // set by XSendEvent
+            send_event: True, // set by XSendEvent
+            display: display.as_raw (),
+            window: target.handle (),
+            message_type: display.intern_atom ("_NET_MOVERESIZE_WINDOW"),
+            format: 32,
+            data: ClientMessageData::from ([
+              // From the spec:
+              // "The bits 8 to 11 indicate the presence of x, y, width and height"
+              // "The bits 12 to 15 indicate the source [...], so 0001 indicates the
+              //  application and 0010 indicates a Pager or a Taskbar."
+              // The gravity takes the lowest byte.
+              gravity as i64 | (0b1111 << 8) | (source << 12),
+              x as i64,
+              y as i64,
+              w as i64,
+              h as i64,
+            ]),
+          },
+        };
+        Window::from_handle (display, display.root ())
+          .send_event (event, SubstructureRedirectMask | SubstructureNotifyMask);
+      }
+      Self::Configure => unsafe {
+        let mut values: XWindowChanges = std::mem::zeroed ();
+        values.x = x;
+        values.y = y;
+        values.width = w as i32;
+        values.height = h as i32;
+        XConfigureWindow (
+          display.as_raw (),
+          target.handle (),
+          (CWX | CWY | CWWidth | CWHeight) as u32,
+          &mut values,
+        );
+      },
+      Self::Auto { gravity, source } => {
+        *self = Self::apply_auto (display, target, geometry, gravity, source);
+        return;
+      }
+    }
+    display.sync (true);
+  }
+
+  /// Applies the methods in turn until one of them has an effect on the
+  /// target window and returns it, `Configure` if none did.
+  fn apply_auto (
+    display: &Display,
+    target: &Window,
+    geometry: (i32, i32, u32, u32),
+    gravity: i32,
+    source: i64,
+  ) -> Self {
+    target.select_input (StructureNotifyMask);
+    let before = target.get_geometry ();
+    for mut method in [
+      Self::Configure,
+      Self::Message { gravity, source },
+      Self::Direct,
+    ] {
+      method.apply (display, target, geometry);
+      // Syncing in `apply` may have discarded the notification already.
+      let after = target
+        .wait_configure (Duration::from_millis (250))
+        .or_else (|| target.get_geometry ());
+      // If the window already had the requested geometry there is nothing to
+      // observe, so the first method is assumed to work.
+      if after != before || after == Some (geometry) {
+        verbose! ("auto method: using {:?}", method);
+        return method;
+      }
+      verbose! ("auto method: {:?} had no effect", method);
+    }
+    Self::Configure
+  }
+}
+
+#[derive(Copy, Clone)]
+enum GridStyle {
+  Full,
+  Dots,
+  None,
+}
+
+impl GridStyle {
+  fn from_str (s: &str) -> Result<Self> {
+    match s.to_lowercase ().as_str () {
+      "full" => Ok (Self::Full),
+      "dots" => Ok (Self::Dots),
+      "none" => Ok (Self::None),
+      _ => bail! ("Invalid grid style"),
+    }
+  }
+}
+
+#[derive(Copy, Clone)]
+enum OutputFormat {
+  Human,
+  Json,
+}
+
+impl OutputFormat {
+  fn from_str (s: &str) -> Result<Self> {
+    match s.to_lowercase ().as_str () {
+      "human" => Ok (Self::Human),
+      "json" => Ok (Self::Json),
+      _ => bail! ("Invalid format"),
+    }
+  }
+}
+
+/// The screen edge at which `--reserve-space` reserves space.
+#[derive(Copy, Clone)]
+enum Edge {
+  Left,
+  Right,
+  Top,
+  Bottom,
+}
+
+impl Edge {
+  fn from_str (s: &str) -> Result<Self> {
+    match s.to_lowercase ().as_str () {
+      "left" => Ok (Self::Left),
+      "right" => Ok (Self::Right),
+      "top" => Ok (Self::Top),
+      "bottom" => Ok (Self::Bottom),
+      _ => bail! ("Invalid edge"),
+    }
+  }
+}
+
+/// The only axis along which pointer motion changes the selection.
+#[derive(Copy, Clone, PartialEq)]
+enum Axis {
+  Horizontal,
+  Vertical,
+}
+
+/// The geometry applied to the target window, printed in the chosen
+/// `OutputFormat`.  The field names are used as-is for the JSON keys.
+struct Placement {
+  /// Absolute x coordinate
+  x: i32,
+  /// Absolute y coordinate
+  y: i32,
+  width: u32,
+  height: u32,
+  /// The resolved target window ID
+  window: XWindow,
+}
+
+impl Placement {
+  fn print (&self, format: OutputFormat) {
+    match format {
+      OutputFormat::Human => {
+        println! ("Resize: {}x{}+{}+{}", self.width, self.height, self.x, self.y);
+      }
+      OutputFormat::Json => {
+        println! (
+          r#"{{"x":{},"y":{},"width":{},"height":{},"window":{}}}"#,
+          self.x, self.y, self.width, self.height, self.window
+        );
+      }
+    }
+  }
+}
+
+/// Resize a window by selecting cells on a grid.
+#[derive(Parser)]
+pub struct Args {
+  /// X window ID, :ACTIVE: to use the window specified in the
+  /// _NET_ACTIVE_WINDOW property, or :SELECT: to click the window
+  #[arg(required_unless_present = "restore")]
+  window: Option<String>,
+  /// "x,y,width,height", each value may be given as a percentage of the
+  /// screen (or monitor) size instead of pixels.  :AUTO: covers the monitor
+  /// containing the target window.
+  #[arg(required_unless_present_any = ["restore", "print_window"])]
+  dimensions: Option<String>,
+  /// "vertical,horizontal", either may also be a list of relative sizes like
+  /// "2:1:1".  Defaults to the GRID_RESIZE_CELLS environment variable
+  cells: Option<String>,
+  /// "red,green,blue" or "red,green,blue,alpha", components are between 0.0
+  /// and 1.0.  Defaults to the GRID_RESIZE_COLOR environment variable or
+  /// "0.898,0.513,0.964"
+  #[arg(long)]
+  color: Option<RGB>,
+  /// Move and resize window as selection changes
+  #[arg(long)]
+  live: bool,
+  /// "configure" to use a configure request, "message" to use a
+  /// _NET_MOVERESIZE_WINDOW client message, "direct" to directly resize the
+  /// X window, or "auto" to try them in that order until the window changes.
+  #[arg(long, default_value_t = {"configure".to_string ()})]
+  method: String,
+  #[arg(long)]
+  /// Whether the drag button (right button by default) should be considered
+  /// pressed at startup
+  right_button_pressed: bool,
+  /// Cancel after this many milliseconds without any input, 0 to never time
+  /// out
+  #[arg(long, default_value_t = 0)]
+  timeout: u64,
+  /// Round the size down to the resize increments requested by the window
+  /// (like terminals do for their character cells)
+  #[arg(long)]
+  respect_hints: bool,
+  /// Space between windows in adjacent cells, in pixels
+  #[arg(long, default_value_t = 0)]
+  gap: u32,
+  /// Space between windows and the edges of the grid, in pixels
+  #[arg(long, default_value_t = 0)]
+  outer_gap: u32,
+  /// Only print the geometry, don't actually move or resize the window
+  #[arg(long)]
+  dry_run: bool,
+  /// "human" or "json", how to print the resulting geometry
+  #[arg(long, default_value_t = {"human".to_string ()})]
+  format: String,
+  /// Monitor index, :ACTIVE: to use the monitor containing the pointer, or
+  /// :PRIMARY:.  The dimensions are then relative to that monitor.  With
+  /// :ACTIVE: and --live the grid follows the pointer to other monitors.
+  #[arg(long)]
+  monitor: Option<String>,
+  /// "top-left", "top-right", "bottom-left", or "bottom-right", the corner
+  /// from which cells are counted
+  #[arg(long, default_value_t = {"top-left".to_string ()})]
+  origin: String,
+  /// Snap the pointer to edges of other windows within this many pixels, 0 to
+  /// disable snapping
+  #[arg(long, default_value_t = 0)]
+  snap_distance: u32,
+  /// Restore the geometry the last resized window had before grid-resize
+  /// changed it, without showing the grid
+  #[arg(long)]
+  restore: bool,
+  /// X button number of the button that applies the selection
+  #[arg(long, default_value_t = Button1)]
+  confirm_button: u32,
+  /// X button number of the button that moves the selection while held
+  #[arg(long, default_value_t = Button3)]
+  drag_button: u32,
+  /// X button number of the button that resets the selection to the cell
+  /// under the pointer [default: 2 unless that is the confirm or drag button]
+  #[arg(long)]
+  reset_button: Option<u32>,
+  /// Clicking the confirm button without moving the pointer selects only the
+  /// cell under the pointer
+  #[arg(long)]
+  click_select: bool,
+  /// Start the selection by pressing the confirm button and apply it when
+  /// the button is released, instead of clicking after moving the pointer
+  #[arg(long)]
+  hold_to_confirm: bool,
+  /// The X display to connect to, defaults to $DISPLAY
+  #[arg(long)]
+  display: Option<String>,
+  /// "full" to draw all grid lines, "dots" to only mark their intersections,
+  /// or "none"
+  #[arg(long, default_value_t = {"full".to_string ()})]
+  grid_style: String,
+  /// Opacity of the black background of the grid, between 0.0 and 1.0
+  #[arg(long, default_value_t = 0.0)]
+  dim: f64,
+  /// Width of the grid lines, the size of the dots also depends on this
+  #[arg(long, default_value_t = 3.0)]
+  line_width: f64,
+  /// Color of the grid lines, same format as --color which is used by default
+  #[arg(long)]
+  line_color: Option<RGB>,
+  /// Color of the selection, same format as --color which is used by default
+  #[arg(long)]
+  selection_color: Option<RGB>,
+  /// Color of the cell under the pointer, same format as --color, defaults to
+  /// a lighter version of --color
+  #[arg(long)]
+  hover_color: Option<RGB>,
+  /// "width:height", shrink selections so their shape matches this aspect
+  /// ratio as closely as the grid allows, 0:0 to disable
+  #[arg(long)]
+  aspect_lock: Option<String>,
+  /// "columnsxrows", selections always span at least this many cells
+  #[arg(long, default_value_t = {"1x1".to_string ()})]
+  min_cells: String,
+  /// How many times per second pointer motion updates the selection, 0 to
+  /// apply every motion event
+  #[arg(long, default_value_t = 30)]
+  motion_hz: u32,
+  /// Class name in the WM_CLASS property of the overlay window
+  #[arg(long, default_value_t = {"Grid_resize".to_string ()})]
+  window_class: String,
+  /// Instance name in the WM_CLASS property of the overlay window
+  #[arg(long, default_value_t = {"grid_resize".to_string ()})]
+  window_instance: String,
+  /// Print the ID of the target window in hexadecimal and exit, mostly useful
+  /// to find out what :ACTIVE: refers to
+  #[arg(long)]
+  print_window: bool,
+  /// Use the given window as it is, even if it is a window manager frame
+  /// around the actual client window
+  #[arg(long)]
+  no_client_walk: bool,
+  /// Take the target out of fullscreen before resizing it
+  #[arg(long)]
+  unfullscreen: bool,
+  /// Whether to take the target out of the maximized state before resizing
+  /// it, defaults to true unless the method is "direct"
+  #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+  unmaximize: Option<bool>,
+  /// Restrict the grid to the work area set by the window manager, which
+  /// excludes panels and docks
+  #[arg(long)]
+  work_area: bool,
+  /// Wait for the window to be resized and warn if its geometry differs from
+  /// the requested one
+  #[arg(long)]
+  verify: bool,
+  /// "column,row:column,row", resize the window to span the cells from the
+  /// first to the last one without showing the grid
+  #[arg(long)]
+  preset: Option<String>,
+  /// Name of a layout in ~/.config/grid-resize/layouts.toml, which gives the
+  /// cells and preset
+  #[arg(long, conflicts_with_all = ["cells", "preset"])]
+  layout: Option<String>,
+  /// After applying a selection, click the next window to resize instead of
+  /// exiting.  Escape or clicking the desktop exits.
+  #[arg(long = "loop")]
+  repeat: bool,
+  /// Cursor shown over the grid, the name or number of a glyph from the X
+  /// cursor font
+  #[arg(long, default_value_t = {"crosshair".to_string ()})]
+  cursor: String,
+  /// Label the cells like a spreadsheet (A1, B2, ...) and select them by
+  /// typing the label, this replaces the hjkl, u, x, and y keys
+  #[arg(long)]
+  labels: bool,
+  /// Factor for the line width and text sizes, defaults to the Xft.dpi
+  /// resource divided by 96
+  #[arg(long)]
+  scale: Option<f64>,
+  /// Move one more cell per key repeat every this many repeats while a
+  /// movement key is held down, 0 to always move one cell
+  #[arg(long, default_value_t = 0)]
+  key_accel: u32,
+  /// Gravity for the "message" method, how the window manager interprets the
+  /// position: "north-west", "north", "north-east", "west", "center", "east",
+  /// "south-west", "south", "south-east", or "static"
+  #[arg(long, default_value_t = {"north-west".to_string ()})]
+  gravity: String,
+  /// Who the "message" method claims to be: "app" or "pager"
+  #[arg(long, default_value_t = {"pager".to_string ()})]
+  source_indication: String,
+  /// Keep the size of the target window and only center it in the selection
+  #[arg(long)]
+  center_in_cell: bool,
+  /// Maximum width of the window in pixels or as a percentage of the
+  /// monitor, it is centered in the selection if that is wider
+  #[arg(long)]
+  max_width: Option<String>,
+  /// Maximum height of the window in pixels or as a percentage of the
+  /// monitor, it is centered in the selection if that is taller
+  #[arg(long)]
+  max_height: Option<String>,
+  /// Don't flash the selection when it is applied
+  #[arg(long)]
+  no_flash: bool,
+  /// Milliseconds over which the window is moved to its new geometry with
+  /// the "configure" and "direct" methods, 0 to apply it at once
+  #[arg(long, default_value_t = 0)]
+  animate_duration: u64,
+  /// Opacity of the whole overlay between 0.0 and 1.0, requires a compositor
+  #[arg(long)]
+  opacity: Option<f64>,
+  /// Place the window and all other normal windows in the grid cells in
+  /// row-major order without showing the grid
+  #[arg(long, conflicts_with_all = ["preset", "layout", "repeat"])]
+  tile_all: bool,
+  /// ID of a window to ignore when snapping and with --tile-all, may be
+  /// given multiple times
+  #[arg(long = "exclude-window", value_name = "ID")]
+  exclude_windows: Vec<String>,
+  /// Space in pixels to leave around the whole grid inside the dimensions
+  #[arg(long, default_value_t = 0)]
+  margin: u32,
+  /// Cancel when the overlay loses the keyboard focus
+  #[arg(long)]
+  quit_on_focus_loss: bool,
+  /// Log the target window, method, cells, and state changes to stderr
+  #[arg(long)]
+  verbose: bool,
+  /// Move this window to where the target was after applying the selection,
+  /// or :AUTO: for the window under the center of the selection
+  #[arg(long, value_name = "WINDOW")]
+  swap_with: Option<String>,
+  /// Make all cells square, CELLS is then a single number of cells along the
+  /// shorter side of the grid
+  #[arg(long)]
+  square_cells: bool,
+  /// ID of a window whose aspect ratio the grid is shrunk to inside the
+  /// dimensions, the dimensions are used as given if it does not exist
+  #[arg(long, value_name = "WINDOW")]
+  grid_from_window: Option<String>,
+  /// "x1,y1,x2,y2", cells to select when the grid is shown instead of the
+  /// cell under the pointer
+  #[arg(long, conflicts_with_all = ["preset", "layout", "tile_all"])]
+  initial_selection: Option<String>,
+  /// Reserve the space between the window and this screen edge (left, right,
+  /// top, or bottom) so other windows avoid it, like a panel
+  #[arg(long, value_name = "EDGE")]
+  reserve_space: Option<String>,
+  /// Raise and activate the window through the window manager after
+  /// resizing it
+  #[arg(long)]
+  activate: bool,
+  /// Apply the selection with Space and keep the grid open, Enter applies it
+  /// and exits
+  #[arg(long)]
+  keep_open: bool,
+  /// Draw directly to the overlay instead of an off-screen buffer, for
+  /// debugging drawing issues
+  #[arg(long)]
+  no_double_buffer: bool,
+}
+
+impl Args {
+  fn parse_list<T> (list: &str) -> StdResult<Vec<T>, T::Err>
+  where
+    T: FromStr,
+    T::Err: std::fmt::Debug,
+  {
+    Self::parse_list_by (list, ',')
+  }
+
+  fn parse_list_by<T> (list: &str, separator: char) -> StdResult<Vec<T>, T::Err>
+  where
+    T: FromStr,
+    T::Err: std::fmt::Debug,
+  {
+    list.split (separator).map (|elem| elem.parse ()).collect ()
+  }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone)]
+struct RGB {
+  red: f64,
+  green: f64,
+  blue: f64,
+  /// Overrides the default opacity of whatever is drawn with this color.
+  alpha: Option<f64>,
+}
+
+impl RGB {
+  fn new (red: f64, green: f64, blue: f64) -> Self {
+    Self {
+      red,
+      green,
+      blue,
+      alpha: None,
+    }
+  }
+
+  fn lerp (&self, other: RGB, w: f64) -> RGB {
+    Self {
+      alpha: self.alpha,
+      ..Self::new (
+        self.red + w * (other.red - self.red),
+        self.green + w * (other.green - self.green),
+        self.blue + w * (other.blue - self.blue),
+      )
+    }
+  }
+
+  /// Returns either black or white, whichever is more readable on top of this
+  /// color.
+  fn contrasting (&self) -> RGB {
+    let luminance = 0.299 * self.red + 0.587 * self.green + 0.114 * self.blue;
+    if luminance > 0.5 {
+      Self::new (0.0, 0.0, 0.0)
+    } else {
+      Self::new (1.0, 1.0, 1.0)
+    }
+  }
+}
+
+impl FromStr for RGB {
+  type Err = String;
+
+  fn from_str (s: &str) -> StdResult<Self, Self::Err> {
+    const NAMES: [&str; 4] = ["red", "green", "blue", "alpha"];
+    let list = Args::parse_list::<f64> (s).map_err (|e| e.to_string ())?;
+    if list.len () != 3 && list.len () != 4 {
+      return Err (format! (
+        "expected 3 color components (or 4 with alpha), got {}",
+        list.len ()
+      ));
+    }
+    for (value, name) in list.iter ().zip (NAMES) {
+      if !(0.0..=1.0).contains (value) {
+        return Err (format! (
+          "{} component must be between 0.0 and 1.0, got {}",
+          name, value
+        ));
+      }
+    }
+    Ok (Self {
+      red: list[0],
+      green: list[1],
+      blue: list[2],
+      alpha: list.get (3).copied (),
+    })
+  }
+}
+
+thread_local! {
+  /// Set by `error_handler` when a request failed with `BadWindow`.
+  static BAD_WINDOW: Cell<bool> = const { Cell::new (false) };
+}
+
+/// Returns whether a `BadWindow` error occurred since the last call.  Errors
+/// are only received once the request was flushed, so this should be called
+/// after syncing.
+fn take_bad_window () -> bool {
+  BAD_WINDOW.with (|flag| flag.replace (false))
+}
+
+unsafe extern "C" fn error_handler (display: XDisplay, event: *mut XErrorEvent) -> i32 {
+  if (*event).error_code == BadWindow {
+    BAD_WINDOW.with (|flag| flag.set (true));
+  }
+  const ERROR_TEXT_SIZE: usize = 1024;
+  let mut error_text_buf: [c_char; ERROR_TEXT_SIZE] = [0; ERROR_TEXT_SIZE];
+  let error_text = &mut error_text_buf as *mut c_char;
+  XGetErrorText (
+    display,
+    (*event).error_code as i32,
+    error_text,
+    ERROR_TEXT_SIZE as i32,
+  );
+  let error_msg = CStr::from_ptr (error_text).to_str ().unwrap ().to_owned ();
+  eprintln! ("X Error: {}", error_msg);
+  0
+}
+
+fn get_active_window (display: &Display) -> Result<XWindow> {
+  let prop = display.intern_atom ("_NET_ACTIVE_WINDOW");
+  let window = display
+    .get_property::<XWindow> (display.root (), prop, XA_WINDOW)
+    .ok_or ("No active window")?
+    .first ()
+    .copied ()
+    .ok_or ("Malformed _NET_ACTIVE_WINDOW property")?;
+  // Some window managers use None or the root window when nothing is focused.
+  if window == 0 || window == display.root () {
+    bail! ("No active window to resize");
+  }
+  Ok (window)
+}
+
+/// Lets the user click a window and returns the top-level window below the
+/// pointer.  Pressing Escape cancels the selection.
+fn select_window (display: &Display) -> Result<XWindow> {
+  let root = Window::from_handle (display, display.root ());
+  let cursor = display.create_font_cursor (XC_CROSSHAIR);
+  let grab = ScopedPointerGrab::grab (
+    display,
+    &root,
+    ButtonPressMask | ButtonReleaseMask,
+    cursor,
+  );
+  if grab.is_none () {
+    display.free_cursor (cursor);
+    bail! ("Failed to grab the pointer");
+  }
+  // Clicking still works if this fails, only Escape does not.
+  let keyboard_grab = ScopedKeyboardGrab::grab (display, &root);
+  let mut event: XEvent = unsafe { std::mem::zeroed () };
+  let mut selected = NONE;
+  // Wait for the release as well so it does not end up going to the target.
+  loop {
+    display.next_event (&mut event);
+    #[allow(non_upper_case_globals)]
+    match unsafe { event.type_ } {
+      ButtonPress => selected = unsafe { event.button.subwindow },
+      ButtonRelease => break,
+      KeyPress if x::lookup_keysym (unsafe { &event.key }) == x11::keysym::XK_Escape as KeySym => {
+        selected = NONE;
+        break;
+      }
+      _ => {}
+    }
+  }
+  drop (keyboard_grab);
+  drop (grab);
+  display.free_cursor (cursor);
+  if selected == NONE {
+    bail! ("No window selected");
+  }
+  Ok (selected)
+}
+
+/// Returns whether a key press at `time` repeats the last pressed key.
+fn is_key_repeat (last_key: Option<(u32, Time)>, keysym: u32, time: Time) -> bool {
+  // Longer than the usual auto-repeat intervals but shorter than pressing
+  // a key again.
+  const MAX_REPEAT_INTERVAL: Time = 150;
+  match last_key {
+    // Server time is 32 bits and wraps around every ~49.7 days.
+    Some ((last, last_time)) => {
+      last == keysym
+        && ((time as u32).wrapping_sub (last_time as u32) as Time) < MAX_REPEAT_INTERVAL
+    }
+    None => false,
+  }
+}
+
+/// Parses a window ID given in decimal or, with a `0x` prefix, hexadecimal.
+fn parse_window_id (s: &str) -> Result<XWindow> {
+  let id = match s.strip_prefix ("0x") {
+    Some (hex) => XWindow::from_str_radix (hex, 16),
+    None => s.parse (),
+  };
+  id.map_err (|_| format! ("Invalid window ID: `{}`", s).into ())
+}
+
+/// Returns the window given by the `window` argument.  If `client_walk` is
+/// set and the window is a window manager frame, the client window inside it
+/// is returned instead.
+fn resolve_window (display: &Display, window: &str, client_walk: bool) -> Result<XWindow> {
+  let window = if window == ":ACTIVE:" {
+    get_active_window (display)?
+  } else if window == ":SELECT:" {
+    select_window (display)?
+  } else {
+    parse_window_id (window)?
+  };
+  if client_walk {
+    Ok (Window::from_handle (display, window).find_client ().unwrap_or (window))
+  } else {
+    Ok (window)
+  }
+}
+
+/// Parses a single value of the `dimensions` argument, either in pixels or,
+/// if it ends with `%`, as a percentage of `total`.
+fn parse_dimension (s: &str, total: u32) -> Result<i64> {
+  let value = if let Some (percent) = s.strip_suffix ('%') {
+    percent
+      .parse::<f64> ()
+      .ok ()
+      .map (|percent| (percent / 100.0 * total as f64).round () as i64)
+  } else {
+    s.parse ().ok ()
+  };
+  match value {
+    Some (value) => Ok (value),
+    None => bail! (format! ("Invalid dimension value: `{}`", s)),
+  }
+}
+
+/// Shrinks a span to at most `max` pixels, parsed like a dimension relative
+/// to `total`, keeping its center.
+fn clamp_centered (position: i32, size: u32, max: &Option<String>, total: u32) -> (i32, u32) {
+  // The maximum is validated when parsing the arguments.
+  let Some (max) = max.as_deref ().and_then (|max| parse_dimension (max, total).ok ()) else {
+    return (position, size);
+  };
+  let max = max.clamp (1, u32::MAX as i64) as u32;
+  if size <= max {
+    (position, size)
+  } else {
+    (position + ((size - max) / 2) as i32, max)
+  }
+}
+
+/// Shrinks the rectangle by `margin` pixels on every side.
+fn apply_margin (rect: (i32, i32, u32, u32), margin: u32) -> Result<(i32, i32, u32, u32)> {
+  let (x, y, width, height) = rect;
+  if margin == 0 {
+    return Ok (rect);
+  }
+  if margin.checked_mul (2).is_none_or (|margins| margins >= width.min (height)) {
+    bail! ("The margin is too large for the grid dimensions");
+  }
+  Ok ((
+    x + margin as i32,
+    y + margin as i32,
+    width - 2 * margin,
+    height - 2 * margin,
+  ))
+}
+
+/// Shrinks the rectangle to the aspect ratio of the given window, keeping its
+/// center.  The rectangle is returned as is if the window no longer exists.
+fn match_window_aspect (
+  display: &Display,
+  rect: (i32, i32, u32, u32),
+  window: Option<XWindow>,
+) -> (i32, i32, u32, u32) {
+  let Some (window) = window else {
+    return rect;
+  };
+  let Some ((_, _, window_width, window_height)) =
+    Window::from_handle (display, window).get_geometry ()
+  else {
+    verbose! ("reference window 0x{:x} is gone, using the dimensions as given", window);
+    return rect;
+  };
+  let (x, y, width, height) = rect;
+  if window_width == 0 || window_height == 0 {
+    return rect;
+  }
+  let (window_width, window_height) = (window_width as u64, window_height as u64);
+  if width as u64 * window_height > height as u64 * window_width {
+    let new_width = (height as u64 * window_width / window_height) as u32;
+    (x + ((width - new_width) / 2) as i32, y, new_width, height)
+  } else {
+    let new_height = (width as u64 * window_height / window_width) as u32;
+    (x, y + ((height - new_height) / 2) as i32, width, new_height)
+  }
+}
+
+fn rect_contains (rect: (i32, i32, u32, u32), x: i32, y: i32) -> bool {
+  let (rx, ry, rw, rh) = rect;
+  x >= rx && y >= ry && x < rx + rw as i32 && y < ry + rh as i32
+}
+
+/// Resolves the `dimensions` argument relative to the given area.  If `clamp`
+/// is set the resulting rectangle is restricted to that area.
+fn resolve_dimensions (
+  dimensions: &str,
+  area: (i32, i32, u32, u32),
+  clamp: bool,
+) -> Result<(i32, i32, u32, u32)> {
+  let dimensions = if dimensions == ":AUTO:" {
+    "0,0,100%,100%"
+  } else {
+    dimensions
+  };
+  let dimensions: Vec<&str> = dimensions.split (',').collect ();
+  if dimensions.len () != 4 {
+    bail! ("Invalid dimensions, should be: `x,y,width,height`");
+  }
+  let (area_x, area_y, area_width, area_height) = area;
+  let mut x = area_x + parse_dimension (dimensions[0], area_width)? as i32;
+  let mut y = area_y + parse_dimension (dimensions[1], area_height)? as i32;
+  let width = parse_dimension (dimensions[2], area_width)?;
+  let height = parse_dimension (dimensions[3], area_height)?;
+  if width < 0 || height < 0 {
+    bail! ("Invalid dimensions, width and height must not be negative");
+  }
+  let mut width = width as u32;
+  let mut height = height as u32;
+  if clamp {
+    x = x.clamp (area_x, area_x + area_width as i32);
+    y = y.clamp (area_y, area_y + area_height as i32);
+    width = width.min ((area_x + area_width as i32 - x) as u32);
+    height = height.min ((area_y + area_height as i32 - y) as u32);
+  }
+  Ok ((x, y, width, height))
+}
+
+/// Returns the rectangle of the monitor given by the `--monitor` argument.
+fn get_monitor (display: &Display, monitor: &str) -> Result<(i32, i32, u32, u32)> {
+  find_monitor (display.monitors (), monitor, || display.query_pointer_position ())
+}
+
+/// Returns the rectangle of the monitor given by the `--monitor` argument
+/// from the list of monitors.  `pointer` is only called for `:ACTIVE:`.
+fn find_monitor (
+  monitors: Vec<Monitor>,
+  monitor: &str,
+  pointer: impl FnOnce () -> Option<(i32, i32)>,
+) -> Result<(i32, i32, u32, u32)> {
+  if monitor == ":ACTIVE:" {
+    let (x, y) = pointer ().ok_or ("Failed to get pointer position")?;
+    let monitor = monitors
+      .into_iter ()
+      .find (|monitor| rect_contains (monitor.rect, x, y))
+      .ok_or ("No monitor contains the pointer")?;
+    Ok (monitor.rect)
+  } else if monitor == ":PRIMARY:" {
+    let monitor = monitors
+      .into_iter ()
+      .find (|monitor| monitor.primary)
+      .ok_or ("There is no primary monitor")?;
+    Ok (monitor.rect)
+  } else {
+    let index: usize = monitor.parse ()?;
+    match monitors.get (index) {
+      Some (monitor) => Ok (monitor.rect),
+      None => bail! (format! ("Invalid monitor index: {}", index)),
+    }
+  }
+}
+
+/// Returns the rectangle of the monitor containing the center of the given
+/// window.
+fn get_window_monitor (display: &Display, window: &Window) -> Result<(i32, i32, u32, u32)> {
+  let (x, y, w, h) = window
+    .get_geometry ()
+    .ok_or ("Failed to get target window geometry")?;
+  let (center_x, center_y) = (x + w as i32 / 2, y + h as i32 / 2);
+  let monitor = display
+    .monitors ()
+    .into_iter ()
+    .find (|monitor| rect_contains (monitor.rect, center_x, center_y))
+    .ok_or ("Target window is not on any monitor")?;
+  Ok (monitor.rect)
+}
+
+/// Parses one component of the `cells` argument, either a number of equally
+/// sized cells or a `:` separated list of weights.
+fn parse_weights (s: &str) -> Result<Vec<u32>> {
+  let weights = if s.contains (':') {
+    Args::parse_list_by (s, ':').ok ()
+  } else {
+    match s.parse::<usize> () {
+      Ok (count) if count > u16::MAX as usize => bail! ("Invalid grid size, too many cells"),
+      Ok (count) => Some (vec![1; count]),
+      Err (_) => None,
+    }
+  };
+  match weights {
+    Some (weights) if weights.is_empty () => bail! ("Invalid grid size, need at least one cell"),
+    Some (weights) if weights.contains (&0) => bail! ("Invalid grid size, weights must not be 0"),
+    Some (weights) if weights.iter ().try_fold (0u32, |sum, &w| sum.checked_add (w)).is_none () => {
+      bail! ("Invalid grid size, weights are too large")
+    }
+    Some (weights) => Ok (weights),
+    None => bail! (format! ("Invalid grid size value: `{}`", s)),
+  }
+}
+
+/// Parses the `--aspect-lock` argument, a ratio with a zero component disables
+/// the lock.
+fn parse_aspect_ratio (s: &Option<String>) -> Result<Option<(u32, u32)>> {
+  let Some (s) = s else {
+    return Ok (None);
+  };
+  match Args::parse_list_by::<u32> (s, ':').as_deref () {
+    Ok ([width, height]) if *width == 0 || *height == 0 => Ok (None),
+    Ok (&[width, height]) => Ok (Some ((width, height))),
+    _ => bail! (format! ("Invalid aspect ratio, should be `width:height`: `{}`", s)),
+  }
+}
+
+/// Parses the `--preset` argument, the indices of the first and last cell.
+fn parse_preset (s: &str) -> Result<((u32, u32), (u32, u32))> {
+  let cells: Option<Vec<Vec<u32>>> = s
+    .split (':')
+    .map (|cell| Args::parse_list (cell).ok ())
+    .collect ();
+  match cells.as_deref () {
+    Some ([first, last]) if first.len () == 2 && last.len () == 2 => {
+      Ok (((first[0], first[1]), (last[0], last[1])))
+    }
+    _ => bail! (format! ("Invalid preset, should be `column,row:column,row`: `{}`", s)),
+  }
+}
+
+/// Parses the `--initial-selection` argument, the indices of the first and
+/// last cell.
+fn parse_initial_selection (s: &str) -> Result<((u32, u32), (u32, u32))> {
+  match Args::parse_list::<u32> (s).as_deref () {
+    Ok (&[x1, y1, x2, y2]) => Ok (((x1, y1), (x2, y2))),
+    _ => bail! (format! ("Invalid initial selection, should be `x1,y1,x2,y2`: `{}`", s)),
+  }
+}
+
+/// Sets up the drawing state shared by the overlay and its back buffer.
+fn configure_context (context: &Context, line_width: f64, scale: f64) {
+  context.set_operator (Operator::Source);
+  context.set_line_width (line_width);
+  context.select_font_face ("sans-serif", FontSlant::Normal, FontWeight::Bold);
+  context.set_font_size (16.0 * scale);
+}
+
+/// Creates the off-screen surface frames are drawn to before they are shown.
+fn create_back_buffer (
+  width: u32,
+  height: u32,
+  line_width: f64,
+  scale: f64,
+) -> Result<(ImageSurface, Context)> {
+  let surface = ImageSurface::create (Format::ARgb32, width as i32, height as i32)?;
+  let context = Context::new (&surface)?;
+  configure_context (&context, line_width, scale);
+  Ok ((surface, context))
+}
+
+/// Returns the glyph in the X cursor font for the `--cursor` argument, see
+/// `X11/cursorfont.h` for the numbers.
+fn cursor_shape (s: &str) -> Result<c_uint> {
+  match s {
+    "crosshair" => Ok (XC_CROSSHAIR),
+    "cross" => Ok (30),
+    "tcross" => Ok (130),
+    "plus" => Ok (90),
+    "target" => Ok (128),
+    "dotbox" => Ok (40),
+    "fleur" => Ok (52),
+    "left_ptr" => Ok (68),
+    _ => match s.parse () {
+      // Glyphs come in pairs of the cursor and its mask.
+      Ok (shape) if shape % 2 == 0 && shape < 154 => Ok (shape),
+      _ => bail! (format! ("Invalid cursor: `{}`", s)),
+    },
+  }
+}
+
+/// Returns the scale factor for the `Xft.dpi` resource, relative to the
+/// standard 96 DPI.
+fn detect_scale (display: &Display) -> f64 {
+  display
+    .get_default ("Xft", "dpi")
+    .and_then (|dpi| dpi.parse::<f64> ().ok ())
+    .filter (|&dpi| dpi > 0.0)
+    .map_or (1.0, |dpi| dpi / 96.0)
+}
+
+/// Parses the `--gravity` argument.
+fn parse_gravity (s: &str) -> Result<i32> {
+  match s.to_lowercase ().as_str () {
+    "north-west" => Ok (NorthWestGravity),
+    "north" => Ok (NorthGravity),
+    "north-east" => Ok (NorthEastGravity),
+    "west" => Ok (WestGravity),
+    "center" => Ok (CenterGravity),
+    "east" => Ok (EastGravity),
+    "south-west" => Ok (SouthWestGravity),
+    "south" => Ok (SouthGravity),
+    "south-east" => Ok (SouthEastGravity),
+    "static" => Ok (StaticGravity),
+    _ => bail! (format! ("Invalid gravity: `{}`", s)),
+  }
+}
+
+/// Parses the `--source-indication` argument into the value of the source
+/// indication bits.
+fn parse_source_indication (s: &str) -> Result<i64> {
+  match s.to_lowercase ().as_str () {
+    "app" => Ok (1),
+    "pager" => Ok (2),
+    _ => bail! (format! ("Invalid source indication: `{}`", s)),
+  }
+}
+
+/// Parses the `--min-cells` argument.
+fn parse_min_cells (s: &str) -> Result<(u32, u32)> {
+  match Args::parse_list_by::<u32> (s, 'x').as_deref () {
+    Ok (&[columns, rows]) => Ok ((columns, rows)),
+    _ => bail! (format! ("Invalid minimum cells, should be `columnsxrows`: `{}`", s)),
+  }
+}
+
+/// Shrinks the given dimensions to the nearest size allowed by the resize
+/// increments in `hints`, keeping the result centered in the original area.
+fn apply_size_hints (
+  hints: &XSizeHints,
+  x: i32,
+  y: i32,
+  width: u32,
+  height: u32,
+) -> (i32, i32, u32, u32) {
+  if hints.flags & PResizeInc == 0 {
+    return (x, y, width, height);
+  }
+  // ICCCM says to use the minimum size if no base size is given.
+  let (base_width, base_height) = if hints.flags & PBaseSize != 0 {
+    (hints.base_width, hints.base_height)
+  } else if hints.flags & PMinSize != 0 {
+    (hints.min_width, hints.min_height)
+  } else {
+    (0, 0)
+  };
+  let snap = |size: u32, base: i32, inc: i32| {
+    let base = base.max (0) as u32;
+    if inc <= 0 || size <= base {
+      size
+    } else {
+      size - (size - base) % inc as u32
+    }
+  };
+  let use_width = snap (width, base_width, hints.width_inc);
+  let use_height = snap (height, base_height, hints.height_inc);
+  let use_x = x + (width - use_width) as i32 / 2;
+  let use_y = y + (height - use_height) as i32 / 2;
+  (use_x, use_y, use_width, use_height)
+}
+
+/// Returns the positions of the vertical and horizontal edges of all visible
+/// top-level windows except the excluded ones, relative to the given origin.
+fn window_edges (
+  display: &Display,
+  exclude: &[XWindow],
+  origin: (i32, i32),
+) -> (Vec<i32>, Vec<i32>) {
+  let mut vertical = Vec::new ();
+  let mut horizontal = Vec::new ();
+  for handle in Window::from_handle (display, display.root ()).children () {
+    let window = Window::from_handle (display, handle);
+    if exclude.contains (&handle) || !window.is_viewable () {
+      continue;
+    }
+    if let Some ((x, y, w, h)) = window.get_geometry () {
+      vertical.push (x - origin.0);
+      vertical.push (x + w as i32 - origin.0);
+      horizontal.push (y - origin.1);
+      horizontal.push (y + h as i32 - origin.1);
+    }
+  }
+  (vertical, horizontal)
+}
+
+/// Returns the edge closest to `value` if it is no farther than `distance`.
+/// The result stays on the same side of the edge as `value` so it ends up in
+/// the grid cell next to the edge on that side.
+fn snap (value: i32, edges: &[i32], distance: u32) -> Option<i32> {
+  let edge = edges
+    .iter ()
+    .copied ()
+    .filter (|edge| edge.abs_diff (value) <= distance)
+    .min_by_key (|edge| edge.abs_diff (value))?;
+  Some (if value < edge { edge - 1 } else { edge })
+}
+
+fn rectangle_with_gradient (
+  context: &Context,
+  x: f64,
+  y: f64,
+  w: f64,
+  h: f64,
+  color: &RGB,
+) -> Result<()> {
+  let cx = x + w / 2.0;
+  let cy = y + h / 2.0;
+  // It seems like one cannot use transate/scale to create an elliptical
+  // gradient, so we just use a mix of the width and height for it's size
+  // as the next best choice.
+  let aspect = w / h;
+  let radius = (w / aspect + h * aspect) / 2.0;
+  let alpha = color.alpha.unwrap_or (0.8);
+  let gradient = RadialGradient::new (cx, cy, 0.0, cx, cy, radius);
+  gradient.add_color_stop_rgba (0.0, color.red, color.green, color.blue, alpha / 4.0);
+  gradient.add_color_stop_rgba (1.0, color.red, color.green, color.blue, alpha);
+  context.set_source (&gradient)?;
+  context.rectangle (x, y, w, h);
+  context.fill ()?;
+  Ok (())
+}
+
+/// Draws the lines of the grid, and the cell labels if their font size is
+/// given, into a mask the size of the grid.  They are then painted in the line
+/// color through it.
+fn draw_grid_lines (
+  grid: &Grid,
+  style: GridStyle,
+  line_width: f64,
+  label_size: Option<f64>,
+) -> Result<ImageSurface> {
+  let width = *grid.columns.last ().unwrap ();
+  let height = *grid.rows.last ().unwrap ();
+  let surface = ImageSurface::create (Format::A8, width as i32, height as i32)?;
+  let context = Context::new (&surface)?;
+  context.set_line_width (line_width);
+  match style {
+    GridStyle::Full => {
+      for &x in grid.columns.iter () {
+        let x = x as f64;
+        context.move_to (x, 0.0);
+        context.line_to (x, height as f64);
+        context.stroke ()?;
+      }
+      for &y in grid.rows.iter () {
+        let y = y as f64;
+        context.move_to (0.0, y);
+        context.line_to (width as f64, y);
+        context.stroke ()?;
+      }
+    }
+    GridStyle::Dots => {
+      let radius = line_width * 1.5;
+      for &x in grid.columns.iter () {
+        for &y in grid.rows.iter () {
+          context.arc (x as f64, y as f64, radius, 0.0, 2.0 * std::f64::consts::PI);
+          context.fill ()?;
+        }
+      }
+    }
+    GridStyle::None => {}
+  }
+  if let Some (label_size) = label_size {
+    context.select_font_face ("sans-serif", FontSlant::Normal, FontWeight::Bold);
+    context.set_font_size (label_size);
+    let padding = line_width + label_size / 3.0;
+    for column in 0..grid.vertical_cells {
+      for row in 0..grid.horizontal_cells {
+        let (x, y, w, h) = grid.rect ((column, row), (column + 1, row + 1));
+        let label = grid.cell_label ((column, row));
+        let extents = context.text_extents (&label)?;
+        if extents.width + 2.0 * padding > w as f64 || extents.height + 2.0 * padding > h as f64 {
+          continue;
+        }
+        context.move_to (
+          x as f64 + padding - extents.x_bearing,
+          y as f64 + padding - extents.y_bearing,
+        );
+        context.show_text (&label)?;
+      }
+    }
+  }
+  surface.flush ();
+  Ok (surface)
+}
+
+/// Everything that is drawn for one frame of the overlay.
+struct Frame<'a> {
+  size: (u32, u32),
+  dim: f64,
+  selection_color: RGB,
+  hover_color: RGB,
+  line_color: RGB,
+  /// Mask from `draw_grid_lines`.
+  grid_lines: &'a ImageSurface,
+  scale: f64,
+  selection: (i32, i32, u32, u32),
+  /// Text shown in the selection.
+  label: String,
+  /// The cell under the pointer.
+  hover: Option<(i32, i32, u32, u32)>,
+  snapped: (Option<i32>, Option<i32>),
+  /// Area of the axis lock indicator.
+  axis_lock: Option<(i32, i32, u32, u32)>,
+}
+
+/// Draws the overlay onto `context`, which needs to use the `Source`
+/// operator.
+fn draw_frame (context: &Context, frame: &Frame) -> Result<()> {
+  let (width, height) = frame.size;
+  // Clear.  Since the selection is drawn with the `Source` operator it
+  // replaces the dimming instead of being drawn on top of it.
+  context.set_source_rgba (0.0, 0.0, 0.0, frame.dim);
+  context.paint ()?;
+  // Pending area
+  {
+    let (x, y, w, h) = frame.selection;
+    rectangle_with_gradient (
+      context,
+      x as f64,
+      y as f64,
+      w as f64,
+      h as f64,
+      &frame.selection_color,
+    )?;
+    centered_text (
+      context,
+      &frame.label,
+      x as f64,
+      y as f64,
+      w as f64,
+      h as f64,
+      &frame.selection_color.contrasting (),
+    )?;
+  }
+  // Cell under mouse
+  if let Some ((x, y, w, h)) = frame.hover {
+    context.set_operator (Operator::Over);
+    rectangle_with_gradient (
+      context,
+      x as f64,
+      y as f64,
+      w as f64,
+      h as f64,
+      &frame.hover_color,
+    )?;
+    context.set_operator (Operator::Source);
+  }
+  // Lines
+  let line_color = &frame.line_color;
+  context.set_source_rgba (
+    line_color.red,
+    line_color.green,
+    line_color.blue,
+    line_color.alpha.unwrap_or (0.9),
+  );
+  context.mask_surface (frame.grid_lines, 0.0, 0.0)?;
+  // Snap guides
+  context.set_dash (&[6.0 * frame.scale, 4.0 * frame.scale], 0.0);
+  if let Some (x) = frame.snapped.0 {
+    context.move_to (x as f64, 0.0);
+    context.line_to (x as f64, height as f64);
+    context.stroke ()?;
+  }
+  if let Some (y) = frame.snapped.1 {
+    context.move_to (0.0, y as f64);
+    context.line_to (width as f64, y as f64);
+    context.stroke ()?;
+  }
+  context.set_dash (&[], 0.0);
+  // Axis lock, fainter than the grid lines so it does not look like one.
+  if let Some ((x, y, w, h)) = frame.axis_lock {
+    context.set_source_rgba (line_color.red, line_color.green, line_color.blue, 0.4);
+    context.rectangle (x as f64, y as f64, w as f64, h as f64);
+    context.fill ()?;
+  }
+  Ok (())
+}
+
+/// Draws `text` centered in the given rectangle, nothing is drawn if it does
+/// not fit.
+fn centered_text (
+  context: &Context,
+  text: &str,
+  x: f64,
+  y: f64,
+  w: f64,
+  h: f64,
+  color: &RGB,
+) -> Result<()> {
+  let extents = context.text_extents (text)?;
+  if extents.width > w || extents.height > h {
+    return Ok (());
+  }
+  context.set_source_rgb (color.red, color.green, color.blue);
+  context.move_to (
+    x + (w - extents.width) / 2.0 - extents.x_bearing,
+    y + (h - extents.height) / 2.0 - extents.y_bearing,
+  );
+  context.show_text (text)?;
+  Ok (())
+}
+
+/// The grid overlay for resizing one window at a time.
+pub struct GridResize {
+  display: Display,
+  window: Window,
+  gc: GC,
+  cursor: Cursor,
+  surface: Surface,
+  context: Context,
+  /// Frames are drawn here and then copied to `surface` at once, so the
+  /// individual drawing steps are never visible.  `None` to draw to
+  /// `surface` directly.
+  back_buffer: Option<(ImageSurface, Context)>,
+  x: i32,
+  y: i32,
+  width: u32,
+  height: u32,
+  /// Geometries requested by `fit_grid` whose `ConfigureNotify` has not
+  /// arrived yet.
+  pending_configures: Vec<(i32, i32, u32, u32)>,
+  target: Window,
+  grid: Grid,
+  selection: Selection,
+  buttons: Buttons,
+  running: bool,
+  quit_on_focus_loss: bool,
+  /// Whether the last selection was applied instead of cancelled.
+  confirmed: bool,
+  /// Pick another window and show the grid again after applying a selection.
+  repeat: bool,
+  client_walk: bool,
+  /// Show cell labels and select cells by typing them.
+  labels: bool,
+  /// The part of a cell label typed so far.
+  label_input: String,
+  /// Factor for the sizes of lines and text.
+  scale: f64,
+  /// Number of key repeats after which movement keys move one more cell.
+  key_accel: u32,
+  /// The last pressed key and when it was pressed.
+  last_key: Option<(u32, Time)>,
+  key_repeats: u32,
+  line_color: RGB,
+  selection_color: RGB,
+  hover_color: RGB,
+  last_box: ((u32, u32), (u32, u32)),
+  live: bool,
+  last_motion: Instant,
+  /// Minimum time between applying two motion events, `None` to apply all.
+  motion_interval: Option<Duration>,
+  /// Pointer position of a motion event that was throttled.
+  pending_motion: Option<(i32, i32)>,
+  method: MoveResizeMethod,
+  timeout: Option<Duration>,
+  last_activity: Instant,
+  respect_hints: bool,
+  center_in_cell: bool,
+  max_width: Option<String>,
+  max_height: Option<String>,
+  /// Fade out a highlight of the selection after applying it.
+  flash: bool,
+  /// How long the target takes to move to its new geometry.
+  animate_duration: Option<Duration>,
+  dry_run: bool,
+  format: OutputFormat,
+  dimensions: String,
+  /// The area the grid is fitted into.
+  bounds: (i32, i32, u32, u32),
+  /// Number of square cells along the shorter side of the bounds, the number
+  /// of cells along the other side depends on the size of the bounds.
+  square_cells: Option<u32>,
+  /// Window whose aspect ratio the bounds are shrunk to.
+  grid_from_window: Option<XWindow>,
+  margin: u32,
+  monitor: (i32, i32, u32, u32),
+  follow_monitor: bool,
+  work_area: bool,
+  undo_stack: Vec<(i32, i32, u32, u32)>,
+  snap_distance: u32,
+  /// Windows ignored for snapping and tiling, including the overlay.
+  excluded: Vec<XWindow>,
+  swap_with: Option<String>,
+  snap_edges: (Vec<i32>, Vec<i32>),
+  snapped: (Option<i32>, Option<i32>),
+  drawn_snapped: (Option<i32>, Option<i32>),
+  axis_lock: Option<Axis>,
+  /// Pointer position relative to the overlay when `hover` was last updated.
+  pointer: Option<(i32, i32)>,
+  /// The cell under the pointer.
+  hover: Option<(i32, i32, u32, u32)>,
+  reserve_space: Option<Edge>,
+  activate: bool,
+  keep_open: bool,
+  /// Areas of the previous redraw that depend on the selection, pointer, or
+  /// snapping, `None` if the whole surface needs to be redrawn.
+  drawn_regions: Option<Vec<(i32, i32, u32, u32)>>,
+  grid_style: GridStyle,
+  /// The grid lines only change with the grid geometry so they are drawn once
+  /// into this mask.
+  grid_lines: ImageSurface,
+  dim: f64,
+  unfullscreen: bool,
+  unmaximize: bool,
+  verify: bool,
+  /// The geometry last requested for the target, in live mode it is only
+  /// verified when finishing.
+  applied: Option<(i32, i32, u32, u32)>,
+}
+
+impl GridResize {
+  /// Creates the overlay for the window given by `config`, it is not shown
+  /// until [`run`](Self::run) is called.
+  pub fn new (display: Display, config: Config) -> Result<Self> {
+    let config = config.validate ()?;
+    x::set_error_handler (error_handler);
+    VERBOSE.store (config.verbose, Ordering::Relaxed);
+    let target = resolve_window (&display, &config.window, config.client_walk)?;
+    let target = Window::from_handle (&display, target);
+    verbose! ("target window: 0x{:x}", target.handle ());
+    verbose! ("method: {:?}", config.method);
+    if config.verify {
+      target.select_input (StructureNotifyMask);
+    }
+
+    let auto_dimensions = config.dimensions == ":AUTO:";
+    let monitor = match &config.monitor {
+      Some (monitor) => get_monitor (&display, monitor)?,
+      None if auto_dimensions => get_window_monitor (&display, &target)?,
+      None => {
+        let (screen_width, screen_height) = display.screen_size ();
+        (0, 0, screen_width, screen_height)
+      }
+    };
+    let area = if config.work_area {
+      display.work_area (monitor)
+    } else {
+      monitor
+    };
+    let bounds = match_window_aspect (
+      &display,
+      apply_margin (
+        resolve_dimensions (
+          &config.dimensions,
+          area,
+          config.monitor.is_some () || auto_dimensions || config.work_area,
+        )?,
+        config.margin,
+      )?,
+      config.grid_from_window,
+    );
+
+    let (column_weights, row_weights, (x, y, width, height)) = match config.square_cells {
+      Some (count) => {
+        let (rect, (columns, rows)) = square_grid (bounds, count)?;
+        (vec![1; columns as usize], vec![1; rows as usize], rect)
+      }
+      None => {
+        let (x, y, width, height) = bounds;
+        let rect = correct_dimensions (
+          x,
+          y,
+          width,
+          height,
+          config.column_weights.iter ().sum (),
+          config.row_weights.iter ().sum (),
+        )?;
+        (config.column_weights, config.row_weights, rect)
+      }
+    };
+
+    let vi = display
+      .match_visual_info (32, TrueColor)
+      .ok_or ("Failed to get RGBA visual")?;
+    let colormap = display.create_colormap (vi.visual, AllocNone);
+
+    let window = Window::builder (&display)
+      .position (x, y)
+      .size (width, height)
+      .attributes (|attributes| {
+        attributes
+          .override_redirect (true)
+          .background_pixel (0)
+          .border_pixel (0)
+          .event_mask (
+            ButtonPressMask
+              | ButtonReleaseMask
+              | PointerMotionMask
+              | LeaveWindowMask
+              | FocusChangeMask
+              | StructureNotifyMask,
+          )
+          .colormap (colormap)
+          .save_under (true);
+      })
+      .depth (vi.depth)
+      .visual (vi.visual)
+      .build ();
+    window.set_class_hint (&config.window_class, &config.window_instance);
+    let cursor = display.create_font_cursor (config.cursor);
+    window.define_cursor (cursor);
+    unsafe {
+      let desktop_type = display.intern_atom ("_NET_WM_WINDOW_TYPE_DESKTOP");
+      XChangeProperty (
+        display.as_raw (),
+        window.handle (),
+        display.intern_atom ("_NET_WM_WINDOW_TYPE"),
+        XA_ATOM,
+        32,
+        PropModeReplace,
+        &desktop_type as *const u64 as *const c_uchar,
+        1,
+      );
+      if let Some (opacity) = config.opacity {
+        // Compositors read this as a fraction of the maximum cardinal value,
+        // without one running the property has no effect.
+        let value = (opacity * u32::MAX as f64).round () as u64;
+        XChangeProperty (
+          display.as_raw (),
+          window.handle (),
+          display.intern_atom ("_NET_WM_WINDOW_OPACITY"),
+          XA_CARDINAL,
+          32,
+          PropModeReplace,
+          &value as *const u64 as *const c_uchar,
+          1,
+        );
+      }
+    }
+
+    let gc = unsafe {
+      XCreateGC (
+        display.as_raw (),
+        window.handle (),
+        0,
+        std::ptr::null_mut (),
+      )
+    };
+
+    let surface = unsafe {
+      let raw = cairo_xlib_surface_create (
+        display.as_raw (),
+        window.handle (),
+        vi.visual,
+        width as i32,
+        height as i32,
+      );
+      Surface::from_raw_full (raw)?
+    };
+
+    let context = Context::new (&surface)?;
+    let scale = config.scale.unwrap_or_else (|| detect_scale (&display));
+    configure_context (&context, config.line_width * scale, scale);
+    let back_buffer = if config.double_buffer {
+      Some (create_back_buffer (width, height, config.line_width * scale, scale)?)
+    } else {
+      None
+    };
+
+    let (mouse_x, mouse_y) = display
+      .query_pointer_position ()
+      .ok_or ("Failed to get pointer position")?;
+
+    let mut excluded = config.exclude;
+    excluded.push (window.handle ());
+    let snap_edges = if config.snap_distance > 0 {
+      window_edges (&display, &[&excluded[..], &[target.handle ()]].concat (), (x, y))
+    } else {
+      (Vec::new (), Vec::new ())
+    };
+
+    let grid = Grid::new (width, height, column_weights, row_weights)
+      .with_gaps (config.gap, config.outer_gap)
+      .with_origin (config.origin)
+      .with_aspect_lock (config.aspect_lock)
+      .with_min_cells (config.min_cells);
+    let selection = match config.initial_selection {
+      Some ((first, last)) => Selection::from_cells (&grid, first, last).ok_or_else (|| {
+        format! (
+          "Invalid initial selection, the grid only has {}x{} cells",
+          grid.vertical_cells, grid.horizontal_cells
+        )
+      })?,
+      None => Selection::new (mouse_x - x, mouse_y - y),
+    };
+    let grid_lines = draw_grid_lines (
+      &grid,
+      config.grid_style,
+      config.line_width * scale,
+      config.labels.then_some (12.0 * scale),
+    )?;
+
+    Ok (Self {
+      display,
+      window,
+      gc,
+      cursor,
+      surface,
+      context,
+      back_buffer,
+      x,
+      y,
+      width,
+      height,
+      pending_configures: Vec::new (),
+      target,
+      grid,
+      selection,
+      buttons: Buttons::new (
+        config.confirm_button,
+        config.drag_button,
+        config.reset_button,
+        config.right_button_pressed,
+      )
+      .with_click_select (config.click_select)
+      .with_hold_to_confirm (config.hold_to_confirm),
+      running: false,
+      quit_on_focus_loss: config.quit_on_focus_loss,
+      confirmed: false,
+      repeat: config.repeat,
+      client_walk: config.client_walk,
+      labels: config.labels,
+      label_input: String::new (),
+      scale,
+      key_accel: config.key_accel,
+      last_key: None,
+      key_repeats: 0,
+      line_color: config.line_color,
+      selection_color: config.selection_color,
+      hover_color: config.hover_color,
+      last_box: ((0, 0), (0, 0)),
+      live: config.live,
+      last_motion: Instant::now (),
+      motion_interval: config.motion_interval,
+      pending_motion: None,
+      method: config.method,
+      timeout: config.timeout,
+      last_activity: Instant::now (),
+      respect_hints: config.respect_hints,
+      center_in_cell: config.center_in_cell,
+      max_width: config.max_width,
+      max_height: config.max_height,
+      flash: config.flash,
+      animate_duration: config.animate_duration,
+      dry_run: config.dry_run,
+      format: config.format,
+      follow_monitor: config.live && config.monitor.as_deref () == Some (":ACTIVE:"),
+      dimensions: config.dimensions,
+      bounds,
+      square_cells: config.square_cells,
+      grid_from_window: config.grid_from_window,
+      margin: config.margin,
+      monitor,
+      work_area: config.work_area,
+      verify: config.verify,
+      applied: None,
+      undo_stack: Vec::new (),
+      snap_distance: config.snap_distance,
+      excluded,
+      swap_with: config.swap_with,
+      snap_edges,
+      snapped: (None, None),
+      drawn_snapped: (None, None),
+      axis_lock: None,
+      pointer: None,
+      hover: None,
+      reserve_space: config.reserve_space,
+      activate: config.activate,
+      keep_open: config.keep_open,
+      drawn_regions: None,
+      grid_style: config.grid_style,
+      grid_lines,
+      dim: config.dim,
+      unfullscreen: config.unfullscreen,
+      unmaximize: config.unmaximize,
+    })
+  }
+
+  /// Shows the grid and resizes the target to the selected cells, the
+  /// display is closed once this returns.
+  pub fn run (&mut self) -> Result<()> {
+    let applied = loop {
+      let applied = self.select ()?;
+      if !(applied && self.repeat) {
+        break applied;
+      }
+      // The overlay would be in the way of picking the next window.
+      self.window.unmap ();
+      self.display.sync (false);
+      match select_window (&self.display) {
+        Ok (target) => self.set_target (target),
+        Err (_) => break applied,
+      }
+    };
+    // With --activate the window manager already focused it if the selection
+    // was applied, unless this is a dry run.  Otherwise we still go through
+    // the window manager if there is one, setting the focus ourselves
+    // bypasses it and it may just revert it.
+    if !(applied && self.activate && !self.dry_run) {
+      if self.display.has_ewmh_wm () {
+        if !self.target.activate () {
+          verbose! ("failed to send _NET_ACTIVE_WINDOW");
+        }
+      } else {
+        self.display.set_input_focus (self.target);
+      }
+    }
+    self.close ();
+    Ok (())
+  }
+
+  /// Shows the grid and lets the user select the cells for the current
+  /// target, returns `true` if the selection was applied and `false` if it
+  /// was cancelled.
+  fn select (&mut self) -> Result<bool> {
+    self.window.map_raised ();
+    // Keyboard input while moving/resizing a window is weird so we joink the
+    // keyboard as long as we are running.
+    let keyboard_grab = ScopedKeyboardGrab::grab (&self.display, &self.window);
+    if keyboard_grab.is_none () {
+      eprintln! ("Failed to grab the keyboard");
+    }
+    // Same for the pointer so we get all motion and button events, even if
+    // the pointer is over a different window.
+    let pointer_grab = ScopedPointerGrab::grab (
+      &self.display,
+      &self.window,
+      ButtonPressMask | ButtonReleaseMask | PointerMotionMask,
+      self.cursor,
+    );
+    if pointer_grab.is_none () {
+      eprintln! ("Failed to grab the pointer");
+    }
+    self.pointer = None;
+    self.update_hover ();
+    self.redraw ()?;
+    let mut event: XEvent = unsafe { std::mem::zeroed () };
+    self.running = true;
+    self.confirmed = false;
+    self.last_activity = Instant::now ();
+    while self.running {
+      if self.timed_out () {
+        self.cancel ();
+        continue;
+      }
+      if self.wait_event (&mut event) {
+        // Only the latest pointer position matters, so a burst of motion
+        // events is handled as one.
+        self.display.coalesce_motion (&mut event);
+        self.handle_event (&event)?;
+      } else {
+        // Woken up to apply a throttled motion.
+        self.apply_motion ();
+      }
+      // The hovered cell is only recomputed if the pointer moved and only
+      // repainted if it changed.
+      let hover_changed = self.update_hover ();
+      let box_ = self.selection.get (&self.grid);
+      if box_ != self.last_box {
+        self.redraw ()?;
+        self.last_box = box_;
+        if self.live {
+          self.move_and_resize ()?;
+        }
+      } else if self.snapped != self.drawn_snapped || hover_changed {
+        self.redraw ()?;
+      }
+    }
+    // The grabs need to be released while the connection is still open.
+    drop (pointer_grab);
+    drop (keyboard_grab);
+    Ok (self.confirmed)
+  }
+
+  /// Switches to a new target window and resets the selection to the cell
+  /// under the pointer.
+  fn set_target (&mut self, target: XWindow) {
+    let target = Window::from_handle (&self.display, target);
+    self.target = if self.client_walk {
+      Window::from_handle (&self.display, target.find_client ().unwrap_or (target.handle ()))
+    } else {
+      target
+    };
+    verbose! ("target window: 0x{:x}", self.target.handle ());
+    self.update_snap_edges ();
+    if self.verify {
+      self.target.select_input (StructureNotifyMask);
+    }
+    self.undo_stack.clear ();
+    self.applied = None;
+    if let Some ((x, y)) = self.display.query_pointer_in (&self.window) {
+      self.selection = Selection::new (x, y);
+    }
+    self.last_box = ((0, 0), (0, 0));
+    self.drawn_regions = None;
+  }
+
+  /// Moves and resizes the target to span the given cells without showing
+  /// the grid, this closes the display like [`run`](Self::run).
+  pub fn apply_preset (&mut self, preset: ((u32, u32), (u32, u32))) -> Result<()> {
+    let (first, last) = preset;
+    let Some (selection) = Selection::from_cells (&self.grid, first, last) else {
+      bail! (format! (
+        "Invalid preset, the grid only has {}x{} cells",
+        self.grid.vertical_cells, self.grid.horizontal_cells
+      ));
+    };
+    self.selection = selection;
+    self.move_and_resize ()?;
+    self.reserve_space ();
+    if self.activate {
+      self.activate ();
+    }
+    self.close ();
+    Ok (())
+  }
+
+  /// Places the target in the first cell and the other normal windows from
+  /// the client list in the following cells in row-major order, without
+  /// showing the grid.  This closes the display like [`run`](Self::run).
+  pub fn tile_all (&mut self) -> Result<()> {
+    let first = self.target.handle ();
+    let mut windows = vec![first];
+    windows.extend (
+      self
+        .client_list ()
+        .into_iter ()
+        .filter (|&window| window != first && self.is_tileable (window)),
+    );
+    let columns = self.grid.vertical_cells;
+    let cell_count = (columns * self.grid.horizontal_cells) as usize;
+    if windows.len () > cell_count {
+      eprintln! (
+        "Warning: only {} of {} windows fit into the grid",
+        cell_count,
+        windows.len ()
+      );
+      windows.truncate (cell_count);
+    }
+    for (index, window) in windows.into_iter ().enumerate () {
+      self.target = Window::from_handle (&self.display, window);
+      if self.verify {
+        self.target.select_input (StructureNotifyMask);
+      }
+      let cell = (index as u32 % columns, index as u32 / columns);
+      (self.selection.p1_x, self.selection.p1_y) = self.grid.cell_center (cell);
+      (self.selection.p2_x, self.selection.p2_y) = self.grid.cell_center (cell);
+      self.move_and_resize ()?;
+    }
+    self.close ();
+    Ok (())
+  }
+
+  /// Returns whether the window is a normal window that is not minimized.
+  fn is_tileable (&self, window: XWindow) -> bool {
+    let window = Window::from_handle (&self.display, window);
+    let types = window.get_atoms (self.display.intern_atom ("_NET_WM_WINDOW_TYPE"));
+    // Windows without a type are normal windows.
+    if !types.is_empty ()
+      && !types.contains (&self.display.intern_atom ("_NET_WM_WINDOW_TYPE_NORMAL"))
+    {
+      return false;
+    }
+    let state = window.get_atoms (self.display.intern_atom ("_NET_WM_STATE"));
+    !state.contains (&self.display.intern_atom ("_NET_WM_STATE_HIDDEN"))
+  }
+
+  /// Returns the windows that can be resized, without the overlay and the
+  /// excluded windows.
+  pub fn client_list (&self) -> Vec<XWindow> {
+    let mut clients = self.display.client_list ();
+    clients.retain (|client| !self.excluded.contains (client));
+    clients
+  }
+
+  fn close (&mut self) {
+    self.window.destroy ();
+    self.display.free_cursor (self.cursor);
+    unsafe {
+      XFreeGC (self.display.as_raw (), self.gc);
+    }
+    self.display.close ();
+  }
+
+  fn handle_event (&mut self, event: &XEvent) -> Result<()> {
+    #[allow(non_upper_case_globals)]
+    if matches! (unsafe { event.type_ }, ButtonPress | MotionNotify | KeyPress) {
+      self.last_activity = Instant::now ();
+    }
+    #[allow(non_upper_case_globals)]
+    match unsafe { event.type_ } {
+      ButtonPress => self.button_press (unsafe { &event.button })?,
+      ButtonRelease => self.button_release (unsafe { &event.button })?,
+      MotionNotify => {
+        let motion = unsafe { &event.motion };
+        self.motion (motion);
+        self.follow_pointer (motion.x_root, motion.y_root)?;
+      }
+      LeaveNotify => {
+        let crossing = unsafe { &event.crossing };
+        self.follow_pointer (crossing.x_root, crossing.y_root)?;
+      }
+      KeyPress => self.key_press (unsafe { &event.key })?,
+      ConfigureNotify => {
+        let configure = unsafe { &event.configure };
+        // The target also reports these with --verify.
+        if configure.window == self.window.handle () {
+          self.overlay_configured (configure)?;
+        }
+      }
+      // Sent to all clients without selecting it, for example when the
+      // keyboard layout changes.
+      MappingNotify => {
+        let mapping = unsafe { &event.mapping };
+        if mapping.request == MappingKeyboard || mapping.request == MappingModifier {
+          x::refresh_keyboard_mapping (mapping);
+        }
+      }
+      FocusOut => {
+        let focus = unsafe { &event.focus_change };
+        // Grabbing and releasing the keyboard ourselves also moves the focus.
+        if self.quit_on_focus_loss && focus.mode != NotifyGrab && focus.mode != NotifyUngrab {
+          self.cancel ();
+        }
+      }
+      _ => {}
+    }
+    Ok (())
+  }
+
+  /// Moves the grid to the monitor containing the given point if it changed
+  /// and we are following the pointer.
+  fn follow_pointer (&mut self, x: i32, y: i32) -> Result<()> {
+    if !self.follow_monitor || rect_contains (self.monitor, x, y) {
+      return Ok (());
+    }
+    if let Some (monitor) = self
+      .display
+      .monitors ()
+      .into_iter ()
+      .find (|monitor| rect_contains (monitor.rect, x, y))
+    {
+      self.change_monitor (monitor.rect, x, y)?;
+    }
+    Ok (())
+  }
+
+  /// Rebuilds the grid and overlay for the given monitor, the selection is
+  /// reset to the cell containing the given point.
+  fn change_monitor (
+    &mut self,
+    monitor: (i32, i32, u32, u32),
+    pointer_x: i32,
+    pointer_y: i32,
+  ) -> Result<()> {
+    let area = if self.work_area {
+      self.display.work_area (monitor)
+    } else {
+      monitor
+    };
+    self.bounds = match_window_aspect (
+      &self.display,
+      apply_margin (resolve_dimensions (&self.dimensions, area, true)?, self.margin)?,
+      self.grid_from_window,
+    );
+    self.monitor = monitor;
+    self.fit_grid ()?;
+    self.selection = Selection::new (pointer_x - self.x, pointer_y - self.y);
+    self.last_box = self.selection.get (&self.grid);
+    self.redraw ()?;
+    self.move_and_resize ()
+  }
+
+  /// Recomputes the edges to snap to, after the overlay moved or the target
+  /// changed.
+  fn update_snap_edges (&mut self) {
+    if self.snap_distance > 0 {
+      self.snap_edges = window_edges (
+        &self.display,
+        &[&self.excluded[..], &[self.target.handle ()]].concat (),
+        (self.x, self.y),
+      );
+    }
+  }
+
+  /// Fits the grid into its bounds and moves the overlay to it.
+  fn fit_grid (&mut self) -> Result<()> {
+    let (x, y, width, height) = if let Some (count) = self.square_cells {
+      let (rect, (columns, rows)) = square_grid (self.bounds, count)?;
+      self
+        .grid
+        .set_weights (vec![1; columns as usize], vec![1; rows as usize]);
+      rect
+    } else {
+      let (x, y, width, height) = self.bounds;
+      let (vertical_units, horizontal_units) = self.grid.total_weights ();
+      correct_dimensions (x, y, width, height, vertical_units, horizontal_units)?
+    };
+    self.x = x;
+    self.y = y;
+    self.width = width;
+    self.height = height;
+    self.update_snap_edges ();
+    self.grid.resize (width, height);
+    self.grid_lines = draw_grid_lines (
+      &self.grid,
+      self.grid_style,
+      self.context.line_width (),
+      self.labels.then_some (12.0 * self.scale),
+    )?;
+    self.window.move_and_resize (x, y, width, height);
+    self.pending_configures.push ((x, y, width, height));
+    self.resize_surfaces ()?;
+    // The pointer may be over a different cell now even if it did not move.
+    self.pointer = None;
+    self.update_hover ();
+    Ok (())
+  }
+
+  /// Resizes the drawing surfaces to the size of the overlay, the next
+  /// redraw repaints everything.
+  fn resize_surfaces (&mut self) -> Result<()> {
+    unsafe {
+      cairo_xlib_surface_set_size (
+        self.surface.to_raw_none (),
+        self.width as i32,
+        self.height as i32,
+      );
+    }
+    if self.back_buffer.is_some () {
+      self.back_buffer = Some (create_back_buffer (
+        self.width,
+        self.height,
+        self.context.line_width (),
+        self.scale,
+      )?);
+    }
+    self.drawn_regions = None;
+    Ok (())
+  }
+
+  /// Adapts to the overlay being moved or resized by someone else.  It is
+  /// override-redirect so window managers should leave it alone, but a buggy
+  /// compositor might not.  The grid keeps its size.
+  fn overlay_configured (&mut self, event: &XConfigureEvent) -> Result<()> {
+    let (width, height) = (event.width as u32, event.height as u32);
+    let geometry = (event.x, event.y, width, height);
+    if let Some (index) = self.pending_configures.iter ().position (|&g| g == geometry) {
+      // Our own changes from `fit_grid`, earlier requests were superseded.
+      self.pending_configures.drain (..=index);
+      return Ok (());
+    }
+    if geometry == (self.x, self.y, self.width, self.height) {
+      return Ok (());
+    }
+    verbose! ("overlay configured to {}x{}+{}+{}", width, height, event.x, event.y);
+    (self.x, self.y) = (event.x, event.y);
+    self.update_snap_edges ();
+    if (width, height) != (self.width, self.height) {
+      (self.width, self.height) = (width, height);
+      self.resize_surfaces ()?;
+    }
+    self.pointer = None;
+    self.update_hover ();
+    self.redraw ()
+  }
+
+  /// Adds `delta` columns and rows to the grid, with all cells the same size.
+  /// The selection keeps its corners so it covers the closest cells.
+  fn change_density (&mut self, delta: i32) -> Result<()> {
+    let columns = (self.grid.vertical_cells as i32 + delta).max (1) as u32;
+    let rows = (self.grid.horizontal_cells as i32 + delta).max (1) as u32;
+    let (_, _, max_columns, max_rows) = self.bounds;
+    if columns > max_columns || rows > max_rows {
+      return Ok (());
+    }
+    let (old_x, old_y) = (self.x, self.y);
+    if let Some (count) = &mut self.square_cells {
+      *count = columns.min (rows);
+    } else {
+      self
+        .grid
+        .set_weights (vec![1; columns as usize], vec![1; rows as usize]);
+    }
+    self.fit_grid ()?;
+    let (dx, dy) = (old_x - self.x, old_y - self.y);
+    self.selection.p1_x += dx;
+    self.selection.p1_y += dy;
+    self.selection.p2_x += dx;
+    self.selection.p2_y += dy;
+    self.redraw ()
+  }
+
+  /// Returns whether the inactivity timeout expired.
+  fn timed_out (&self) -> bool {
+    self
+      .timeout
+      .is_some_and (|timeout| self.last_activity.elapsed () >= timeout)
+  }
+
+  /// Waits for the next event, returns `false` if the inactivity timeout
+  /// expired or a throttled motion is due first.
+  fn wait_event (&self, event: &mut XEvent) -> bool {
+    let timeout = self
+      .timeout
+      .map (|timeout| timeout.saturating_sub (self.last_activity.elapsed ()));
+    let motion = self
+      .pending_motion
+      .and (self.motion_interval)
+      .map (|interval| interval.saturating_sub (self.last_motion.elapsed ()));
+    match timeout.into_iter ().chain (motion).min () {
+      Some (timeout) => self.display.next_event_timeout (event, timeout),
+      None => {
+        self.display.next_event (event);
+        true
+      }
+    }
+  }
+
+  /// Updates the cell under the pointer if the pointer moved since the last
+  /// call, returns whether the cell changed.
+  fn update_hover (&mut self) -> bool {
+    let pointer = self.display.query_pointer_in (&self.window);
+    if pointer == self.pointer {
+      return false;
+    }
+    self.pointer = pointer;
+    let hover = pointer.map (|(x, y)| {
+      let (x, y) = self.grid.lower_bound (x, y);
+      self.grid.rect ((x, y), (x + 1, y + 1))
+    });
+    let changed = hover != self.hover;
+    self.hover = hover;
+    changed
+  }
+
+  /// Returns the areas covered by the snap guides.
+  fn snap_guide_regions (&self) -> Vec<(i32, i32, u32, u32)> {
+    let half_width = (self.context.line_width () / 2.0).ceil () as i32;
+    let mut regions = Vec::new ();
+    if let Some (x) = self.snapped.0 {
+      regions.push ((x - half_width, 0, 2 * half_width as u32, self.height));
+    }
+    if let Some (y) = self.snapped.1 {
+      regions.push ((0, y - half_width, self.width, 2 * half_width as u32));
+    }
+    regions
+  }
+
+  /// Returns the line through the selection anchor along the locked axis.
+  fn axis_lock_region (&self) -> Option<(i32, i32, u32, u32)> {
+    let half_width = (self.context.line_width () / 2.0).ceil () as i32;
+    match self.axis_lock? {
+      Axis::Horizontal => Some ((
+        0,
+        self.selection.p1_y - half_width,
+        self.width,
+        2 * half_width as u32,
+      )),
+      Axis::Vertical => Some ((
+        self.selection.p1_x - half_width,
+        0,
+        2 * half_width as u32,
+        self.height,
+      )),
+    }
+  }
+
+  fn redraw (&mut self) -> Result<()> {
+    let selection = self.selection.get_dimensions (&self.grid);
+    let hover = self.hover;
+    let mut regions = self.snap_guide_regions ();
+    regions.extend (self.axis_lock_region ());
+    regions.push (selection);
+    regions.extend (hover);
+    // Only the areas that changed since the last redraw are repainted, which
+    // are the ones drawn then and now.
+    let drawn = self.drawn_regions.replace (regions.clone ());
+    let clip = |context: &Context| {
+      if let Some (drawn) = &drawn {
+        for &(x, y, w, h) in drawn.iter ().chain (&regions) {
+          context.rectangle (x as f64, y as f64, w as f64, h as f64);
+        }
+        context.clip ();
+      }
+    };
+    match &self.back_buffer {
+      Some ((buffer, buffer_context)) => {
+        clip (buffer_context);
+        self.draw (buffer_context, selection, hover)?;
+        buffer_context.reset_clip ();
+        buffer.flush ();
+        clip (&self.context);
+        self.context.set_source_surface (buffer, 0.0, 0.0)?;
+        self.context.paint ()?;
+      }
+      None => {
+        clip (&self.context);
+        self.draw (&self.context, selection, hover)?;
+      }
+    }
+    self.drawn_snapped = self.snapped;
+    self.context.reset_clip ();
+    self.surface.flush ();
+    self.display.flush ();
+    Ok (())
+  }
+
+  /// Draws the overlay for the given selection and hovered cell rectangles
+  /// onto `context`, which needs to use the `Source` operator.
+  fn draw (
+    &self,
+    context: &Context,
+    selection: (i32, i32, u32, u32),
+    hover: Option<(i32, i32, u32, u32)>,
+  ) -> Result<()> {
+    let (target_x, target_y, target_w, target_h) = self.target_geometry ();
+    draw_frame (
+      context,
+      &Frame {
+        size: (self.width, self.height),
+        dim: self.dim,
+        selection_color: self.selection_color,
+        hover_color: self.hover_color,
+        line_color: self.line_color,
+        grid_lines: &self.grid_lines,
+        scale: self.scale,
+        selection,
+        label: format! ("{}x{}+{}+{}", target_w, target_h, target_x, target_y),
+        hover,
+        snapped: self.snapped,
+        axis_lock: self.axis_lock_region (),
+      },
+    )
+  }
+
+  fn button_press (&mut self, event: &XButtonEvent) -> Result<()> {
+    let action = self.buttons.press (event.button, event.x, event.y, event.time);
+    self.button_action (action, event)
+  }
+
+  fn button_release (&mut self, event: &XButtonEvent) -> Result<()> {
+    let action = self.buttons.release (event.button, event.x, event.y, event.time);
+    self.button_action (action, event)
+  }
+
+  fn button_action (&mut self, action: ButtonAction, event: &XButtonEvent) -> Result<()> {
+    match action {
+      ButtonAction::None => {}
+      ButtonAction::Restart => self.selection = Selection::new (event.x, event.y),
+      ButtonAction::MoveAnchor => {
+        self.selection.p1_x = event.x;
+        self.selection.p1_y = event.y;
+      }
+      ButtonAction::Finish { restart } => {
+        if restart {
+          self.selection = Selection::new (event.x, event.y);
+        }
+        return self.finish ();
+      }
+    }
+    Ok (())
+  }
+
+  fn motion (&mut self, event: &XMotionEvent) {
+    self.pending_motion = Some ((event.x, event.y));
+    self.apply_motion ();
+  }
+
+  /// Moves the selection to the last pointer position unless the previous
+  /// motion was applied less than the motion interval ago.  A throttled
+  /// position is applied once the interval has passed even if the pointer
+  /// stopped moving.
+  fn apply_motion (&mut self) {
+    let Some ((x, y)) = self.pending_motion else {
+      return;
+    };
+    if let Some (interval) = self.motion_interval {
+      if self.last_motion.elapsed () < interval {
+        return;
+      }
+    }
+    self.last_motion = Instant::now ();
+    self.pending_motion = None;
+    self.snapped = (
+      snap (x, &self.snap_edges.0, self.snap_distance),
+      snap (y, &self.snap_edges.1, self.snap_distance),
+    );
+    let mut x = self.snapped.0.unwrap_or (x);
+    let mut y = self.snapped.1.unwrap_or (y);
+    match self.axis_lock {
+      Some (Axis::Horizontal) => y = self.selection.p1_y,
+      Some (Axis::Vertical) => x = self.selection.p1_x,
+      None => {}
+    }
+    self.selection.p2_x = x;
+    self.selection.p2_y = y;
+    if self.buttons.anchor_follows () {
+      self.selection.p1_x = x;
+      self.selection.p1_y = y;
+    }
+  }
+
+  fn key_press (&mut self, event: &XKeyEvent) -> Result<()> {
+    use x11::keysym::*;
+    // The keysym is always unshifted, so Shift+l is still `XK_l` here and the
+    // Shift state only decides about `extend`.
+    let (keysym, state) = x::lookup_keysym_with_state (event);
+    let keysym = keysym as u32;
+    let extend = state & ShiftMask != 0;
+    let step = self.key_step (keysym, event.time);
+    if self.labels && self.label_key (keysym, extend) {
+      return Ok (());
+    }
+    #[allow(non_upper_case_globals)]
+    match keysym {
+      XK_Escape => self.cancel (),
+      XK_Return | XK_KP_Enter => return self.finish (),
+      // In live mode the window is already where the selection is.
+      XK_space if self.keep_open && !self.live => return self.move_and_resize (),
+      XK_u => self.undo (),
+      XK_x => return self.toggle_axis_lock (Axis::Horizontal),
+      XK_y => return self.toggle_axis_lock (Axis::Vertical),
+      XK_Left | XK_h => self.selection.move_cursor (&self.grid, -step, 0, extend),
+      XK_Right | XK_l => self.selection.move_cursor (&self.grid, step, 0, extend),
+      XK_plus | XK_equal | XK_KP_Add => return self.change_density (1),
+      XK_minus | XK_KP_Subtract => return self.change_density (-1),
+      XK_Up | XK_k => self.selection.move_cursor (&self.grid, 0, -step, extend),
+      XK_Down | XK_j => self.selection.move_cursor (&self.grid, 0, step, extend),
+      XK_7 | XK_KP_7 | XK_KP_Home => return self.quick_tile (0, 0),
+      XK_8 | XK_KP_8 | XK_KP_Up => return self.quick_tile (1, 0),
+      XK_9 | XK_KP_9 | XK_KP_Prior => return self.quick_tile (2, 0),
+      XK_4 | XK_KP_4 | XK_KP_Left => return self.quick_tile (0, 1),
+      XK_5 | XK_KP_5 | XK_KP_Begin => return self.quick_tile (1, 1),
+      XK_6 | XK_KP_6 | XK_KP_Right => return self.quick_tile (2, 1),
+      XK_1 | XK_KP_1 | XK_KP_End => return self.quick_tile (0, 2),
+      XK_2 | XK_KP_2 | XK_KP_Down => return self.quick_tile (1, 2),
+      XK_3 | XK_KP_3 | XK_KP_Next => return self.quick_tile (2, 2),
+      _ => {}
+    }
+    Ok (())
+  }
+
+  /// Locks pointer motion to the given axis, or unlocks it if it already was.
+  fn toggle_axis_lock (&mut self, axis: Axis) -> Result<()> {
+    self.axis_lock = (self.axis_lock != Some (axis)).then_some (axis);
+    verbose! (
+      "axis lock: {}",
+      match self.axis_lock {
+        Some (Axis::Horizontal) => "horizontal",
+        Some (Axis::Vertical) => "vertical",
+        None => "off",
+      }
+    );
+    // The selection may not change, but the indicator does.
+    self.redraw ()
+  }
+
+  /// Returns how many cells a movement key moves the selection.  With key
+  /// acceleration this grows while the key is held down and repeats.
+  fn key_step (&mut self, keysym: u32, time: Time) -> i32 {
+    if is_key_repeat (self.last_key, keysym, time) {
+      self.key_repeats += 1;
+    } else {
+      self.key_repeats = 0;
+    }
+    self.last_key = Some ((keysym, time));
+    1 + self.key_repeats.checked_div (self.key_accel).unwrap_or (0) as i32
+  }
+
+  /// Selects the given ninth of the grid, counted from the top-left corner
+  /// of the screen, and applies it.  Does nothing if the grid has less than
+  /// 3 cells along either axis.
+  fn quick_tile (&mut self, column: u32, row: u32) -> Result<()> {
+    let (columns, rows) = (self.grid.vertical_cells, self.grid.horizontal_cells);
+    if columns < 3 || rows < 3 {
+      return Ok (());
+    }
+    let (flip_x, flip_y) = self.grid.origin.flips ();
+    let cell = |x: u32, y: u32| {
+      (
+        if flip_x { columns - 1 - x } else { x },
+        if flip_y { rows - 1 - y } else { y },
+      )
+    };
+    let first = cell (column * columns / 3, row * rows / 3);
+    let last = cell ((column + 1) * columns / 3 - 1, (row + 1) * rows / 3 - 1);
+    (self.selection.p1_x, self.selection.p1_y) = self.grid.cell_center (first);
+    (self.selection.p2_x, self.selection.p2_y) = self.grid.cell_center (last);
+    self.finish ()
+  }
+
+  /// Adds the key to the typed cell label and moves the selection to the
+  /// cell once the label is complete, returns `false` if the key cannot be
+  /// part of a label.
+  fn label_key (&mut self, keysym: u32, extend: bool) -> bool {
+    use x11::keysym::*;
+    #[allow(non_upper_case_globals)]
+    match keysym {
+      XK_a..=XK_z => {
+        // A letter after the row number starts a new label.
+        if self.label_input.ends_with (|c: char| c.is_ascii_digit ()) {
+          self.label_input.clear ();
+        }
+        self.label_input.push ((b'A' + (keysym - XK_a) as u8) as char);
+      }
+      XK_0..=XK_9 if !self.label_input.is_empty () => {
+        self.label_input.push ((b'0' + (keysym - XK_0) as u8) as char);
+        if let Some (cell) = self.grid.parse_label (&self.label_input) {
+          self.selection.move_to (&self.grid, cell, extend);
+        }
+      }
+      XK_BackSpace => self.label_input.clear (),
+      _ => return false,
+    }
+    true
+  }
+
+  fn cancel (&mut self) {
+    self.running = false;
+  }
+
+  fn finish (&mut self) -> Result<()> {
+    self.running = false;
+    self.confirmed = true;
+    // If it's in live mode the window was already resized in the mainloop.
+    if !self.live {
+      self.move_and_resize ()?;
+    } else if let (true, Some (applied)) = (self.verify, self.applied) {
+      self.verify_geometry (applied);
+    }
+    self.reserve_space ();
+    if self.activate {
+      self.activate ();
+    }
+    if self.swap_with.is_some () {
+      self.swap ()?;
+    }
+    if self.flash {
+      self.flash ()?;
+    }
+    Ok (())
+  }
+
+  /// Moves the window given by `--swap-with` to the geometry the target had
+  /// before it was moved.
+  fn swap (&mut self) -> Result<()> {
+    // Empty if nothing was moved, like with `--dry-run`.
+    let Some (&geometry) = self.undo_stack.first () else {
+      return Ok (());
+    };
+    let other = match self.swap_with.as_deref () {
+      Some (":AUTO:") => {
+        let (x, y, w, h) = self.target_geometry ();
+        self.window_at (x + w as i32 / 2, y + h as i32 / 2)
+      }
+      Some (window) => Some (resolve_window (&self.display, window, self.client_walk)?),
+      None => None,
+    };
+    let Some (other) = other else {
+      verbose! ("no window to swap with");
+      return Ok (());
+    };
+    let (x, y, width, height) = geometry;
+    Placement {
+      x,
+      y,
+      width,
+      height,
+      window: other,
+    }
+    .print (self.format);
+    self
+      .method
+      .apply (&self.display, &Window::from_handle (&self.display, other), geometry);
+    if take_bad_window () {
+      bail! ("The window to swap with no longer exists");
+    }
+    Ok (())
+  }
+
+  /// Returns the topmost client window other than the target and the
+  /// excluded windows that contains the given point.
+  fn window_at (&self, x: i32, y: i32) -> Option<XWindow> {
+    // Children of the root window are in stacking order from bottom to top.
+    for handle in Window::from_handle (&self.display, self.display.root ())
+      .children ()
+      .into_iter ()
+      .rev ()
+    {
+      let window = Window::from_handle (&self.display, handle);
+      if self.excluded.contains (&handle) || !window.is_viewable () {
+        continue;
+      }
+      if !window.get_geometry ().is_some_and (|rect| rect_contains (rect, x, y)) {
+        continue;
+      }
+      let client = window.find_client ().unwrap_or (handle);
+      if client != self.target.handle () {
+        return Some (client);
+      }
+    }
+    None
+  }
+
+  /// Fades out a highlight of the selection, with the rest of the overlay
+  /// hidden.
+  fn flash (&mut self) -> Result<()> {
+    const STEPS: u32 = 8;
+    const STEP_TIME: Duration = Duration::from_millis (20);
+    let (x, y, w, h) = self.selection.get_dimensions (&self.grid);
+    let color = self.selection_color;
+    let alpha = color.alpha.unwrap_or (0.8);
+    for step in (0..STEPS).rev () {
+      self.context.set_source_rgba (0.0, 0.0, 0.0, 0.0);
+      self.context.paint ()?;
+      self.context.set_source_rgba (
+        color.red,
+        color.green,
+        color.blue,
+        alpha * step as f64 / STEPS as f64,
+      );
+      self.context.rectangle (x as f64, y as f64, w as f64, h as f64);
+      self.context.fill ()?;
+      self.surface.flush ();
+      self.display.flush ();
+      std::thread::sleep (STEP_TIME);
+    }
+    // The overlay no longer shows the grid.
+    self.drawn_regions = None;
+    Ok (())
+  }
+
+  /// Returns the absolute geometry the target window should get for the
+  /// current selection.
+  fn target_geometry (&self) -> (i32, i32, u32, u32) {
+    let (x, y, w, h) = self.selection.get_dimensions (&self.grid);
+    let (x, y) = (self.x + x, self.y + y);
+    let (x, w) = clamp_centered (x, w, &self.max_width, self.monitor.2);
+    let (y, h) = clamp_centered (y, h, &self.max_height, self.monitor.3);
+    if self.center_in_cell {
+      if let Some ((_, _, target_w, target_h)) = self.target.get_geometry () {
+        let x = x + (w as i32 - target_w as i32) / 2;
+        let y = y + (h as i32 - target_h as i32) / 2;
+        return (x, y, target_w, target_h);
+      }
+    }
+    if self.respect_hints {
+      if let Some (hints) = self.target.get_wm_normal_hints () {
+        return apply_size_hints (&hints, x, y, w, h);
+      }
+    }
+    (x, y, w, h)
+  }
+
+  fn move_and_resize (&mut self) -> Result<()> {
+    // This comes first so `--center-in-cell` sees the size the window has
+    // once it is no longer maximized.
+    if !self.dry_run && self.clear_states () && self.center_in_cell {
+      self.target.select_input (StructureNotifyMask);
+      self.target.wait_configure (Duration::from_millis (250));
+    }
+    let (x, y, w, h) = self.target_geometry ();
+    verbose! (
+      "window 0x{:x}: cells {:?}, geometry {}x{}+{}+{}",
+      self.target.handle (),
+      self.selection.get (&self.grid),
+      w,
+      h,
+      x,
+      y
+    );
+    Placement {
+      x,
+      y,
+      width: w,
+      height: h,
+      window: self.target.handle (),
+    }
+    .print (self.format);
+    if self.dry_run {
+      return Ok (());
+    }
+    take_bad_window ();
+    let current = self.target.get_geometry ();
+    if let Some (geometry) = current {
+      if self.undo_stack.is_empty () {
+        save_undo (self.target.handle (), geometry);
+      }
+      self.undo_stack.push (geometry);
+    }
+    if let (Some (from), Some (duration)) = (current, self.animate_duration) {
+      // Animating every change in live mode would make the grid lag behind
+      // the pointer.
+      if !self.live {
+        self.animate (from, (x, y, w, h), duration);
+      }
+    }
+    self.method.apply (&self.display, &self.target, (x, y, w, h));
+    if take_bad_window () {
+      bail! ("Target window no longer exists");
+    }
+    self.applied = Some ((x, y, w, h));
+    // Waiting for the window in live mode would freeze the grid.
+    if self.verify && !self.live {
+      self.verify_geometry ((x, y, w, h));
+    }
+    Ok (())
+  }
+
+  /// Raises the target and asks the window manager to activate it.
+  fn activate (&self) {
+    if self.dry_run {
+      return;
+    }
+    self.target.raise ();
+    if !self.target.activate () {
+      verbose! ("failed to send _NET_ACTIVE_WINDOW");
+    }
+    self.display.flush ();
+  }
+
+  /// Sets `_NET_WM_STRUT_PARTIAL` and `_NET_WM_STRUT` on the target so the
+  /// window manager keeps other windows out of the space between it and the
+  /// `--reserve-space` edge.  The strut stays after we exit, until the
+  /// window is closed or something else changes it.
+  fn reserve_space (&self) {
+    let Some (edge) = self.reserve_space else {
+      return;
+    };
+    if self.dry_run {
+      return;
+    }
+    let (x, y, w, h) = self.target_geometry ();
+    let (screen_width, screen_height) = self.display.screen_size ();
+    let (left, right, top, bottom) = (
+      x.max (0) as u64,
+      (x + w as i32).max (0) as u64,
+      y.max (0) as u64,
+      (y + h as i32).max (0) as u64,
+    );
+    // left, right, top, bottom, then the start and end of each along the edge
+    let mut strut = [0u64; 12];
+    match edge {
+      Edge::Left => {
+        strut[0] = right;
+        (strut[4], strut[5]) = (top, bottom.saturating_sub (1));
+      }
+      Edge::Right => {
+        strut[1] = (screen_width as u64).saturating_sub (left);
+        (strut[6], strut[7]) = (top, bottom.saturating_sub (1));
+      }
+      Edge::Top => {
+        strut[2] = bottom;
+        (strut[8], strut[9]) = (left, right.saturating_sub (1));
+      }
+      Edge::Bottom => {
+        strut[3] = (screen_height as u64).saturating_sub (top);
+        (strut[10], strut[11]) = (left, right.saturating_sub (1));
+      }
+    }
+    verbose! ("window 0x{:x}: strut {:?}", self.target.handle (), &strut[..4]);
+    unsafe {
+      for (property, count) in [("_NET_WM_STRUT_PARTIAL", 12), ("_NET_WM_STRUT", 4)] {
+        XChangeProperty (
+          self.display.as_raw (),
+          self.target.handle (),
+          self.display.intern_atom (property),
+          XA_CARDINAL,
+          32,
+          PropModeReplace,
+          strut.as_ptr () as *const c_uchar,
+          count,
+        );
+      }
+    }
+    self.display.flush ();
+  }
+
+  /// Moves the target through the geometries between `from` and `to` over
+  /// the given duration, except for the final one.  Only done for the
+  /// `Configure` and `Direct` methods since window managers may already
+  /// animate the ones requested with a message.
+  fn animate (
+    &mut self,
+    from: (i32, i32, u32, u32),
+    to: (i32, i32, u32, u32),
+    duration: Duration,
+  ) {
+    const FRAME_TIME: Duration = Duration::from_millis (16);
+    if !matches! (self.method, MoveResizeMethod::Configure | MoveResizeMethod::Direct) {
+      return;
+    }
+    let lerp = |a: i64, b: i64, t: f64| a + ((b - a) as f64 * t).round () as i64;
+    let start = Instant::now ();
+    loop {
+      let t = start.elapsed ().as_secs_f64 () / duration.as_secs_f64 ();
+      if t >= 1.0 {
+        break;
+      }
+      // Ease out cubic
+      let t = 1.0 - (1.0 - t).powi (3);
+      let geometry = (
+        lerp (from.0 as i64, to.0 as i64, t) as i32,
+        lerp (from.1 as i64, to.1 as i64, t) as i32,
+        lerp (from.2 as i64, to.2 as i64, t) as u32,
+        lerp (from.3 as i64, to.3 as i64, t) as u32,
+      );
+      self.method.apply (&self.display, &self.target, geometry);
+      std::thread::sleep (FRAME_TIME);
+    }
+  }
+
+  /// Waits for the target to be configured and reports the geometry it ended
+  /// up with if it differs from the requested one.
+  fn verify_geometry (&self, requested: (i32, i32, u32, u32)) {
+    let format = |(x, y, w, h): (i32, i32, u32, u32)| format! ("{}x{}+{}+{}", w, h, x, y);
+    // There is no notification if the geometry did not change, and the one
+    // for the direct method may have already been discarded when syncing.
+    let actual = self
+      .target
+      .wait_configure (Duration::from_millis (500))
+      .or_else (|| self.target.get_geometry ());
+    match actual {
+      Some (actual) if actual == requested => {}
+      Some (actual) => eprintln! (
+        "Warning: requested {} but the window is {}",
+        format (requested),
+        format (actual)
+      ),
+      None => eprintln! ("Warning: failed to get the geometry of the window"),
+    }
+  }
+
+  /// Takes the target out of states in which the window manager would ignore
+  /// the new geometry, returns whether it was in any of them.
+  fn clear_states (&self) -> bool {
+    if !self.unfullscreen && !self.unmaximize {
+      return false;
+    }
+    let state = self
+      .target
+      .get_atoms (self.display.intern_atom ("_NET_WM_STATE"));
+    let fullscreen = self.display.intern_atom ("_NET_WM_STATE_FULLSCREEN");
+    let mut cleared = false;
+    if self.unfullscreen && state.contains (&fullscreen) {
+      verbose! ("removing the fullscreen state");
+      remove_wm_state (&self.display, &self.target, fullscreen, NONE);
+      cleared = true;
+    }
+    let vertical = self.display.intern_atom ("_NET_WM_STATE_MAXIMIZED_VERT");
+    let horizontal = self.display.intern_atom ("_NET_WM_STATE_MAXIMIZED_HORZ");
+    if self.unmaximize && (state.contains (&vertical) || state.contains (&horizontal)) {
+      verbose! ("removing the maximized state");
+      remove_wm_state (&self.display, &self.target, vertical, horizontal);
+      cleared = true;
+    }
+    cleared
+  }
+
+  /// Restores the geometry the target had before the last resize.
+  fn undo (&mut self) {
+    if let Some (geometry) = self.undo_stack.pop () {
+      self.method.apply (&self.display, &self.target, geometry);
+      self.applied = Some (geometry);
+    }
+  }
+}
+
+/// Asks the window manager to remove up to two `_NET_WM_STATE` atoms from the
+/// target, the second one may be `NONE`.
+fn remove_wm_state (display: &Display, target: &Window, first: Atom, second: Atom) {
+  // See _NET_WM_STATE in https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html
+  const _NET_WM_STATE_REMOVE: i64 = 0;
+  let event = XEvent {
+    client_message: XClientMessageEvent {
+      type_: ClientMessage,
+      serial: 0,
+      send_event: True,
+      display: display.as_raw (),
+      window: target.handle (),
+      message_type: display.intern_atom ("_NET_WM_STATE"),
+      format: 32,
+      data: ClientMessageData::from ([
+        _NET_WM_STATE_REMOVE,
+        first as i64,
+        second as i64,
+        // Source indication, 2 for pagers and taskbars like in the
+        // _NET_MOVERESIZE_WINDOW message.
+        2,
+        0,
+      ]),
+    },
+  };
+  Window::from_handle (display, display.root ())
+    .send_event (event, SubstructureRedirectMask | SubstructureNotifyMask);
+}
+
+/// Returns the path of the file storing the geometry for `--restore`.
+fn undo_file () -> Option<PathBuf> {
+  std::env::var_os ("HOME").map (|home| PathBuf::from (home).join (".grid-resize-undo"))
+}
+
+fn save_undo (window: XWindow, geometry: (i32, i32, u32, u32)) {
+  let (x, y, w, h) = geometry;
+  if let Some (path) = undo_file () {
+    if let Err (error) = std::fs::write (path, format! ("{} {} {} {} {}\n", window, x, y, w, h)) {
+      eprintln! ("Failed to save geometry for undo: {}", error);
+    }
+  }
+}
+
+fn load_undo () -> Result<(XWindow, (i32, i32, u32, u32))> {
+  let path = undo_file ().ok_or ("HOME is not set")?;
+  let content = std::fs::read_to_string (path)?;
+  let values: Vec<&str> = content.split_whitespace ().collect ();
+  if values.len () != 5 {
+    bail! ("Invalid undo file");
+  }
+  Ok ((
+    values[0].parse ()?,
+    (
+      values[1].parse ()?,
+      values[2].parse ()?,
+      values[3].parse ()?,
+      values[4].parse ()?,
+    ),
+  ))
+}
+
+/// Applies the geometry saved by the last run.
+fn restore (display: &Display, args: &Args) -> Result<()> {
+  let (window, (x, y, w, h)) = load_undo ()?;
+  Placement {
+    x,
+    y,
+    width: w,
+    height: h,
+    window,
+  }
+  .print (OutputFormat::from_str (&args.format)?);
+  if !args.dry_run {
+    let mut method = MoveResizeMethod::from_str (&args.method)?
+      .with_gravity (parse_gravity (&args.gravity)?)
+      .with_source (parse_source_indication (&args.source_indication)?);
+    verbose! ("restoring window 0x{:x} with method {:?}", window, method);
+    method.apply (display, &Window::from_handle (display, window), (x, y, w, h));
+    if take_bad_window () {
+      bail! ("Target window no longer exists");
+    }
+  }
+  Ok (())
+}
+
+/// Does what the command-line program does for the given arguments.
+pub fn run (mut args: Args) -> Result<()> {
+  VERBOSE.store (args.verbose, Ordering::Relaxed);
+  if let Some (name) = &args.layout {
+    let layout = layouts::load (name)?;
+    args.cells = Some (layout.cells);
+    args.preset = Some (layout.preset);
+  }
+  let display = Display::connect (args.display.as_deref ())?;
+  x::set_error_handler (error_handler);
+  if args.restore {
+    return restore (&display, &args);
+  }
+  if args.print_window {
+    let window = args.window.as_deref ().ok_or ("Missing window")?;
+    let window = resolve_window (&display, window, !args.no_client_walk)?;
+    println! ("0x{:x}", window);
+    return Ok (());
+  }
+  let mut grid_resize = GridResize::new (display, Config::try_from (&args)?)?;
+  if args.tile_all {
+    grid_resize.tile_all ()
+  } else if let Some (preset) = &args.preset {
+    grid_resize.apply_preset (parse_preset (preset)?)
+  } else {
+    grid_resize.run ()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use x11::keysym::{XK_h, XK_l};
+
+  /// Returns the pixels of an `ARgb32` surface as premultiplied
+  /// `(red, green, blue, alpha)`.
+  fn pixels (surface: &mut ImageSurface) -> Vec<Vec<(u8, u8, u8, u8)>> {
+    let (width, height) = (surface.width () as usize, surface.height () as usize);
+    let stride = surface.stride () as usize;
+    let data = surface.data ().unwrap ();
+    (0..height)
+      .map (|y| {
+        (0..width)
+          .map (|x| {
+            // Native-endian 32-bit ARGB
+            let i = y * stride + x * 4;
+            let argb = u32::from_ne_bytes ([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+            let [alpha, red, green, blue] = argb.to_be_bytes ();
+            (red, green, blue, alpha)
+          })
+          .collect ()
+      })
+      .collect ()
+  }
+
+  #[test]
+  fn dimensions_on_a_monitor_at_a_negative_origin () {
+    let monitor = (-1920, -100, 1920, 1080);
+    assert_eq! (
+      resolve_dimensions ("0,0,50%,100%", monitor, true).unwrap (),
+      (-1920, -100, 960, 1080)
+    );
+    assert_eq! (
+      resolve_dimensions ("50%,10,100%,100%", monitor, true).unwrap (),
+      (-960, -90, 960, 1070)
+    );
+    // Clamped to the monitor
+    assert_eq! (
+      resolve_dimensions ("-100,-100,100%,100%", monitor, true).unwrap (),
+      (-1920, -100, 1920, 1080)
+    );
+    assert_eq! (apply_margin (monitor, 10).unwrap (), (-1910, -90, 1900, 1060));
+    let max = Some ("50%".to_string ());
+    assert_eq! (clamp_centered (-1920, 1920, &max, 1920), (-1440, 960));
+  }
+
+  /// A primary monitor with a second one on its left.
+  fn monitors () -> Vec<Monitor> {
+    vec![
+      Monitor {
+        rect: (0, 0, 1920, 1080),
+        primary: true,
+      },
+      Monitor {
+        rect: (-1280, 0, 1280, 1024),
+        primary: false,
+      },
+    ]
+  }
+
+  #[test]
+  fn key_repeat () {
+    assert! (is_key_repeat (Some ((XK_h, 1000)), XK_h, 1050));
+    assert! (!is_key_repeat (Some ((XK_h, 1000)), XK_h, 1500));
+    assert! (!is_key_repeat (Some ((XK_h, 1000)), XK_l, 1050));
+    assert! (!is_key_repeat (None, XK_h, 1050));
+    // The server time wrapped around between the presses
+    assert! (is_key_repeat (Some ((XK_h, u32::MAX as Time - 50)), XK_h, 50));
+  }
+
+  #[test]
+  fn clamp_centered_to_max () {
+    let max = |s: &str| Some (s.to_string ());
+    assert_eq! (clamp_centered (100, 400, &max ("200"), 1000), (200, 200));
+    assert_eq! (clamp_centered (100, 400, &max ("50%"), 1000), (100, 400));
+    assert_eq! (clamp_centered (100, 400, &None, 1000), (100, 400));
+    assert_eq! (clamp_centered (100, 400, &max ("8589934592"), 1000), (100, 400));
+  }
+
+  #[test]
+  fn margin () {
+    assert_eq! (apply_margin ((10, 20, 300, 200), 10).unwrap (), (20, 30, 280, 180));
+    assert_eq! (apply_margin ((0, 0, 0, 0), 0).unwrap (), (0, 0, 0, 0));
+    assert! (apply_margin ((0, 0, 300, 200), 100).is_err ());
+    assert! (apply_margin ((0, 0, 300, 200), u32::MAX).is_err ());
+  }
+
+  #[test]
+  fn find_monitor_by_argument () {
+    let rect = |monitor: &str, pointer: Option<(i32, i32)>| {
+      find_monitor (monitors (), monitor, || pointer).ok ()
+    };
+    assert_eq! (rect ("0", None), Some ((0, 0, 1920, 1080)));
+    assert_eq! (rect ("1", None), Some ((-1280, 0, 1280, 1024)));
+    assert_eq! (rect ("2", None), None);
+    assert_eq! (rect ("x", None), None);
+    assert_eq! (rect (":PRIMARY:", None), Some ((0, 0, 1920, 1080)));
+    assert_eq! (rect (":ACTIVE:", Some ((100, 100))), Some ((0, 0, 1920, 1080)));
+    assert_eq! (rect (":ACTIVE:", Some ((-1, 1000))), Some ((-1280, 0, 1280, 1024)));
+    // Below the smaller monitor
+    assert_eq! (rect (":ACTIVE:", Some ((-1, 1050))), None);
+    assert_eq! (rect (":ACTIVE:", None), None);
+    let mut no_primary = monitors ();
+    no_primary[0].primary = false;
+    assert! (find_monitor (no_primary, ":PRIMARY:", || None).is_err ());
+  }
+
+  #[test]
+  fn draw_frame_snapshot () {
+    // 3x2 cells of 100x100 pixels
+    let grid = Grid::new (300, 200, vec![1; 3], vec![1; 2]);
+    let grid_lines = draw_grid_lines (&grid, GridStyle::Full, 2.0, None).unwrap ();
+    let mut surface = ImageSurface::create (Format::ARgb32, 300, 200).unwrap ();
+    {
+      let context = Context::new (&surface).unwrap ();
+      configure_context (&context, 2.0, 1.0);
+      let frame = Frame {
+        size: (300, 200),
+        dim: 0.5,
+        selection_color: RGB::new (1.0, 0.0, 0.0),
+        hover_color: RGB::new (0.0, 1.0, 0.0),
+        line_color: RGB::new (0.0, 0.0, 1.0),
+        grid_lines: &grid_lines,
+        scale: 1.0,
+        selection: grid.rect ((0, 0), (1, 1)),
+        label: String::new (),
+        hover: Some (grid.rect ((2, 1), (3, 2))),
+        snapped: (None, None),
+        axis_lock: None,
+      };
+      draw_frame (&context, &frame).unwrap ();
+    }
+    let pixels = pixels (&mut surface);
+
+    // Dimmed cell
+    let (red, green, blue, alpha) = pixels[50][150];
+    assert_eq! ((red, green, blue), (0, 0, 0));
+    assert! ((126..=129).contains (&alpha), "alpha {}", alpha);
+    // Selection and hovered cell
+    assert! (matches! (pixels[10][10], (r, 0, 0, a) if r > 0 && a > 0));
+    assert! (matches! (pixels[150][250], (0, g, 0, a) if g > 0 && a > 0));
+
+    let count = |f: fn ((u8, u8, u8, u8)) -> bool| {
+      pixels.iter ().flatten ().filter (|&&pixel| f (pixel)).count ()
+    };
+    // 2 pixel wide lines at 0, 100, 200, and 300 horizontally and 0, 100,
+    // and 200 vertically, the ones at the edges are cut in half.
+    let line_columns = 1 + 2 + 2 + 1;
+    let line_rows = 1 + 2 + 1;
+    assert_eq! (
+      count (|(r, g, b, _)| b > r && b > g),
+      line_columns * 200 + line_rows * 300 - line_columns * line_rows
+    );
+    // The cells without the lines around them
+    assert_eq! (count (|(r, g, b, _)| r > g && r > b), 98 * 98);
+    assert_eq! (count (|(r, g, b, _)| g > r && g > b), 98 * 98);
+  }
+}
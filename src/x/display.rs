@@ -1,5 +1,15 @@
 use super::{window::ToXWindow, *};
-use std::ffi::CString;
+use std::{ffi::CString, time::Duration};
+use x11::{
+  xinerama::XineramaQueryScreens,
+  xrandr::{XRRFreeMonitors, XRRGetMonitors, XRRQueryExtension, XRRQueryVersion},
+};
+
+#[derive(Copy, Clone)]
+pub struct Monitor {
+  pub rect: (i32, i32, u32, u32),
+  pub primary: bool,
+}
 
 pub struct Display {
   connection: XDisplay,
@@ -8,15 +18,17 @@ pub struct Display {
 }
 
 impl Display {
-  pub fn connect (name: Option<&str>) -> Self {
+  pub fn connect (name: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
     let connection;
     let root;
     let screen;
 
+    let name_cstr = name.map (CString::new).transpose ()?;
     unsafe {
       connection = XOpenDisplay (
-        name
-          .map (|s| s.as_ptr () as *const c_char)
+        name_cstr
+          .as_ref ()
+          .map (|s| s.as_ptr ())
           .unwrap_or (std::ptr::null ()),
       );
       if connection.is_null () {
@@ -24,23 +36,164 @@ impl Display {
           .map (|s| s.to_string ())
           .or_else (|| std::env::var ("DISPLAY").ok ())
           .unwrap_or_default ();
-        panic! ("Could not open display: {}", name);
+        return Err (format! ("Could not open display: {}", name).into ());
       }
       root = XDefaultRootWindow (connection);
       screen = XDefaultScreen (connection);
     }
 
-    Self {
+    Ok (Self {
       connection,
       screen,
       root,
-    }
+    })
   }
 
   pub fn root (&self) -> XWindow {
     self.root
   }
 
+  pub fn screen_size (&self) -> (u32, u32) {
+    unsafe {
+      (
+        XDisplayWidth (self.connection, self.screen) as u32,
+        XDisplayHeight (self.connection, self.screen) as u32,
+      )
+    }
+  }
+
+  /// Returns the monitors from RandR, falling back to Xinerama (where the
+  /// first screen is considered primary) and then to the whole screen.
+  pub fn monitors (&self) -> Vec<Monitor> {
+    self
+      .randr_monitors ()
+      .or_else (|| self.xinerama_monitors ())
+      .unwrap_or_else (|| {
+        let (width, height) = self.screen_size ();
+        vec![Monitor {
+          rect: (0, 0, width, height),
+          primary: true,
+        }]
+      })
+  }
+
+  /// Returns the monitors using `XRRGetMonitors`, which needs RandR 1.5.
+  fn randr_monitors (&self) -> Option<Vec<Monitor>> {
+    unsafe {
+      let (mut event_base, mut error_base) = (0, 0);
+      if XRRQueryExtension (self.connection, &mut event_base, &mut error_base) == 0 {
+        return None;
+      }
+      let (mut major, mut minor) = (0, 0);
+      if XRRQueryVersion (self.connection, &mut major, &mut minor) == 0
+        || (major, minor) < (1, 5)
+      {
+        return None;
+      }
+      let mut count: c_int = 0;
+      let info = XRRGetMonitors (self.connection, self.root, TRUE, &mut count);
+      if info.is_null () {
+        return None;
+      }
+      let monitors: Vec<Monitor> = std::slice::from_raw_parts (info, count as usize)
+        .iter ()
+        .map (|m| Monitor {
+          rect: (m.x, m.y, m.width as u32, m.height as u32),
+          primary: m.primary != 0,
+        })
+        .collect ();
+      XRRFreeMonitors (info);
+      (!monitors.is_empty ()).then_some (monitors)
+    }
+  }
+
+  fn xinerama_monitors (&self) -> Option<Vec<Monitor>> {
+    unsafe {
+      let mut count: c_int = 0;
+      let screens = XineramaQueryScreens (self.connection, &mut count);
+      if screens.is_null () {
+        return None;
+      }
+      let monitors = std::slice::from_raw_parts (screens, count as usize)
+        .iter ()
+        .enumerate ()
+        .map (|(i, s)| Monitor {
+          rect: (s.x_org as i32, s.y_org as i32, s.width as u32, s.height as u32),
+          primary: i == 0,
+        })
+        .collect ();
+      XFree (screens as *mut c_void);
+      Some (monitors)
+    }
+  }
+
+  /// Returns the part of `monitor` inside the work area of the current
+  /// desktop, which excludes space reserved by panels and docks.  If the
+  /// window manager does not set `_NET_WORKAREA` the monitor is returned as
+  /// is.
+  pub fn work_area (&self, monitor: (i32, i32, u32, u32)) -> (i32, i32, u32, u32) {
+    let work_areas = self.get_root_cardinals ("_NET_WORKAREA");
+    let desktop = self
+      .get_root_cardinals ("_NET_CURRENT_DESKTOP")
+      .first ()
+      .copied ()
+      .unwrap_or (0) as usize;
+    let Some (work_area) = work_areas.chunks_exact (4).nth (desktop) else {
+      return monitor;
+    };
+    let (x, y, w, h) = monitor;
+    let left = x.max (work_area[0] as i32);
+    let top = y.max (work_area[1] as i32);
+    let right = (x + w as i32).min ((work_area[0] + work_area[2]) as i32);
+    let bottom = (y + h as i32).min ((work_area[1] + work_area[3]) as i32);
+    if right <= left || bottom <= top {
+      return monitor;
+    }
+    (left, top, (right - left) as u32, (bottom - top) as u32)
+  }
+
+  /// Returns the windows managed by the window manager from
+  /// `_NET_CLIENT_LIST`, in the order they were mapped.
+  pub fn client_list (&self) -> Vec<XWindow> {
+    self
+      .get_property (self.root, self.intern_atom ("_NET_CLIENT_LIST"), XA_WINDOW)
+      .unwrap_or_default ()
+  }
+
+  /// Returns whether an EWMH compliant window manager is running, that is
+  /// the window in the root's `_NET_SUPPORTING_WM_CHECK` exists and refers
+  /// to itself with the same property.
+  pub fn has_ewmh_wm (&self) -> bool {
+    let check = self.intern_atom ("_NET_SUPPORTING_WM_CHECK");
+    let Some (&[child]) = self.get_property::<XWindow> (self.root, check, XA_WINDOW).as_deref ()
+    else {
+      return false;
+    };
+    matches! (
+      self.get_property::<XWindow> (child, check, XA_WINDOW).as_deref (),
+      Some (&[window]) if window == child
+    )
+  }
+
+  fn get_root_cardinals (&self, property: &str) -> Vec<c_ulong> {
+    self
+      .get_property (self.root, self.intern_atom (property), XA_CARDINAL)
+      .unwrap_or_default ()
+  }
+
+  /// Returns the items of a window property, or `None` if the window does not
+  /// have it or it is not of the expected type.  `T` needs to match the
+  /// format of the property, Xlib returns 32-bit items as longs so those are
+  /// read as `c_ulong`, `Atom`, or `XWindow`.
+  pub fn get_property<T: Copy> (
+    &self,
+    window: XWindow,
+    property: Atom,
+    expected_type: Atom,
+  ) -> Option<Vec<T>> {
+    get_window_property (self.connection, window, property, expected_type)?.items (expected_type)
+  }
+
   pub fn close (&mut self) {
     if !self.connection.is_null () {
       unsafe {
@@ -72,6 +225,46 @@ impl Display {
     }
   }
 
+  /// Like `next_event` but gives up after `timeout`, returns `false` if no
+  /// event arrived in time.
+  pub fn next_event_timeout (&self, event_out: &mut XEvent, timeout: Duration) -> bool {
+    unsafe {
+      if XPending (self.connection) == 0 {
+        let mut pfd = libc::pollfd {
+          fd: XConnectionNumber (self.connection),
+          events: libc::POLLIN,
+          revents: 0,
+        };
+        // Rounded up so a remaining fraction of a millisecond does not spin.
+        let millis = timeout.as_nanos ().div_ceil (1_000_000).min (c_int::MAX as u128);
+        if libc::poll (&mut pfd, 1, millis as c_int) <= 0 {
+          return false;
+        }
+      }
+      XNextEvent (self.connection, event_out);
+    }
+    true
+  }
+
+  /// Replaces a `MotionNotify` event with the last one of the motion events
+  /// directly following it in the queue, which are removed.  Events of other
+  /// types stop this so the order of events does not change.
+  pub fn coalesce_motion (&self, event: &mut XEvent) {
+    if event.get_type () != MotionNotify {
+      return;
+    }
+    let mut next: XEvent = unsafe { std::mem::zeroed () };
+    unsafe {
+      while XPending (self.connection) > 0 {
+        XPeekEvent (self.connection, &mut next);
+        if next.get_type () != MotionNotify {
+          break;
+        }
+        XNextEvent (self.connection, event);
+      }
+    }
+  }
+
   pub fn set_input_focus<W: ToXWindow> (&self, window: W) {
     unsafe {
       XSetInputFocus (
@@ -83,7 +276,27 @@ impl Display {
     }
   }
 
+  /// Returns the value of the given resource from the resource database,
+  /// like `Xft.dpi` for the program `Xft` and option `dpi`.
+  pub fn get_default (&self, program: &str, option: &str) -> Option<String> {
+    let program = CString::new (program).ok ()?;
+    let option = CString::new (option).ok ()?;
+    unsafe {
+      let value = XGetDefault (self.connection, program.as_ptr (), option.as_ptr ());
+      if value.is_null () {
+        None
+      } else {
+        Some (CStr::from_ptr (value).to_string_lossy ().into_owned ())
+      }
+    }
+  }
+
   pub fn query_pointer_position (&self) -> Option<(i32, i32)> {
+    self.query_pointer_in (&self.root)
+  }
+
+  /// Returns the pointer position relative to the origin of `window`.
+  pub fn query_pointer_in<W: ToXWindow> (&self, window: &W) -> Option<(i32, i32)> {
     let mut x: c_int = 0;
     let mut y: c_int = 0;
     // Dummy values
@@ -93,13 +306,13 @@ impl Display {
     if unsafe {
       XQueryPointer (
         self.connection,
-        self.root,
+        window.to_xwindow (),
         &mut w,
         &mut w,
-        &mut x,
-        &mut y,
         &mut i,
         &mut i,
+        &mut x,
+        &mut y,
         &mut u,
       )
     } == TRUE
@@ -117,6 +330,16 @@ impl Display {
     }
   }
 
+  pub fn create_font_cursor (&self, shape: c_uint) -> Cursor {
+    unsafe { XCreateFontCursor (self.connection, shape) }
+  }
+
+  pub fn free_cursor (&self, cursor: Cursor) {
+    unsafe {
+      XFreeCursor (self.connection, cursor);
+    }
+  }
+
   pub fn match_visual_info (&self, depth: i32, class: i32) -> Option<XVisualInfo> {
     unsafe {
       let mut vi: XVisualInfo = std::mem::MaybeUninit::zeroed ().assume_init ();
@@ -128,6 +351,7 @@ impl Display {
     }
   }
 
+  #[allow(clippy::not_unsafe_ptr_arg_deref)]
   pub fn create_colormap (&self, visual: *mut Visual, alloc: i32) -> Colormap {
     unsafe { XCreateColormap (self.connection, self.root, visual, alloc) }
   }
@@ -149,13 +373,28 @@ impl ToXDisplay for XDisplay {
   }
 }
 
+/// Calls `grab` until it succeeds, giving up after about 100 milliseconds.
+/// If we are launched by a key or button binding the window manager may still
+/// be holding its grab for a moment.
+fn retry_grab<F: Fn () -> c_int> (grab: F) -> bool {
+  for _ in 0..100 {
+    if grab () == GrabSuccess {
+      return true;
+    }
+    std::thread::sleep (Duration::from_millis (1));
+  }
+  false
+}
+
 pub struct ScopedKeyboardGrab {
   connection: XDisplay,
 }
 
 impl ScopedKeyboardGrab {
-  pub fn grab (display: &Display, window: &Window) -> Self {
-    unsafe {
+  /// Grabs the keyboard so all key events are reported to `window`, returns
+  /// `None` if the grab failed.
+  pub fn grab (display: &Display, window: &Window) -> Option<Self> {
+    let success = retry_grab (|| unsafe {
       XGrabKeyboard (
         display.as_raw (),
         window.handle (),
@@ -163,11 +402,11 @@ impl ScopedKeyboardGrab {
         GrabModeAsync,
         GrabModeAsync,
         CurrentTime,
-      );
-    }
-    Self {
+      )
+    });
+    success.then (|| Self {
       connection: display.as_raw (),
-    }
+    })
   }
 }
 
@@ -178,3 +417,44 @@ impl Drop for ScopedKeyboardGrab {
     }
   }
 }
+
+pub struct ScopedPointerGrab {
+  connection: XDisplay,
+}
+
+impl ScopedPointerGrab {
+  /// Grabs the pointer so all pointer events are reported to `window` and
+  /// `cursor` is shown, returns `None` if the grab failed (for example
+  /// because another client has already grabbed it).
+  pub fn grab (
+    display: &Display,
+    window: &Window,
+    event_mask: c_long,
+    cursor: Cursor,
+  ) -> Option<Self> {
+    let success = retry_grab (|| unsafe {
+      XGrabPointer (
+        display.as_raw (),
+        window.handle (),
+        False,
+        event_mask as c_uint,
+        GrabModeAsync,
+        GrabModeAsync,
+        NONE,
+        cursor,
+        CurrentTime,
+      )
+    });
+    success.then (|| Self {
+      connection: display.as_raw (),
+    })
+  }
+}
+
+impl Drop for ScopedPointerGrab {
+  fn drop (&mut self) {
+    unsafe {
+      XUngrabPointer (self.connection, CurrentTime);
+    }
+  }
+}
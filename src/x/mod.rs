@@ -9,6 +9,8 @@ pub type ErrorHandler = unsafe extern "C" fn (XDisplay, *mut XErrorEvent) -> i32
 pub const NONE: c_ulong = 0;
 pub const FALSE: c_int = 0;
 pub const TRUE: c_int = 1;
+/// The `XC_crosshair` cursor shape from `X11/cursorfont.h`.
+pub const XC_CROSSHAIR: c_uint = 34;
 
 pub mod display;
 pub mod window;
@@ -18,10 +20,106 @@ pub mod window_builder;
 pub use display::Display;
 pub use window::Window;
 
+/// A window property as returned by `XGetWindowProperty`.
+pub struct Property {
+  pub type_: Atom,
+  pub format: c_int,
+  /// The raw items, Xlib stores 32-bit items as longs.
+  data: Vec<u8>,
+}
+
+impl Property {
+  /// Returns the items if the property has the given type and `T` matches
+  /// its format.
+  pub fn items<T: Copy> (&self, type_: Atom) -> Option<Vec<T>> {
+    let item_size = match self.format {
+      8 => 1,
+      16 => std::mem::size_of::<c_short> (),
+      32 => std::mem::size_of::<c_long> (),
+      _ => 0,
+    };
+    if self.type_ != type_ || item_size != std::mem::size_of::<T> () {
+      return None;
+    }
+    Some (
+      self
+        .data
+        .chunks_exact (item_size)
+        .map (|item| unsafe { std::ptr::read_unaligned (item.as_ptr () as *const T) })
+        .collect (),
+    )
+  }
+}
+
+/// Reads up to 1024 items of a window property, or returns `None` if the
+/// window does not have it.  If it is not of `expected_type`, unless that is
+/// `AnyPropertyType`, only its actual type and format are returned.
+pub fn get_window_property (
+  display: XDisplay,
+  window: XWindow,
+  property: Atom,
+  expected_type: Atom,
+) -> Option<Property> {
+  let mut actual_type: Atom = NONE;
+  let mut format: c_int = 0;
+  let mut nitems: c_ulong = 0;
+  let mut bytes_after: c_ulong = 0;
+  let mut data: *mut c_uchar = std::ptr::null_mut ();
+  unsafe {
+    let status = XGetWindowProperty (
+      display,
+      window,
+      property,
+      0,
+      1024,
+      FALSE,
+      expected_type,
+      &mut actual_type,
+      &mut format,
+      &mut nitems,
+      &mut bytes_after,
+      &mut data,
+    );
+    let item_size = match format {
+      8 => 1,
+      16 => std::mem::size_of::<c_short> (),
+      32 => std::mem::size_of::<c_long> (),
+      _ => 0,
+    };
+    let bytes = if data.is_null () {
+      Vec::new ()
+    } else {
+      let bytes = std::slice::from_raw_parts (data, nitems as usize * item_size).to_vec ();
+      XFree (data as *mut c_void);
+      bytes
+    };
+    (status == Success as c_int && actual_type != NONE).then_some (Property {
+      type_: actual_type,
+      format,
+      data: bytes,
+    })
+  }
+}
+
 pub fn set_error_handler (f: ErrorHandler) -> Option<ErrorHandler> {
   unsafe { XSetErrorHandler (Some (f)) }
 }
 
+/// Updates the keyboard mapping used by `lookup_keysym` after a
+/// `MappingNotify` event.
+pub fn refresh_keyboard_mapping (event: &XMappingEvent) {
+  unsafe {
+    XRefreshKeyboardMapping (event as *const XMappingEvent as *mut XMappingEvent);
+  }
+}
+
 pub fn lookup_keysym (event: &XKeyEvent) -> KeySym {
   unsafe { XLookupKeysym (event as *const XKeyEvent as *mut XKeyEvent, 0) }
 }
+
+/// Returns the unshifted keysym of the event and the held modifiers, without
+/// lock modifiers like Caps Lock and Num Lock.
+pub fn lookup_keysym_with_state (event: &XKeyEvent) -> (KeySym, u32) {
+  const MODIFIERS: u32 = ShiftMask | ControlMask | Mod1Mask | Mod4Mask;
+  (lookup_keysym (event), event.state & MODIFIERS)
+}
@@ -1,6 +1,7 @@
 use super::display::ToXDisplay;
 use super::window_builder::WindowBuilder;
 use super::*;
+use std::time::{Duration, Instant};
 
 #[derive(Copy, Clone)]
 pub struct Window {
@@ -35,11 +36,43 @@ impl Window {
     self.handle
   }
 
-  //pub fn raise (&self) {
-  //  unsafe {
-  //    XRaiseWindow (self.display (), self.handle);
-  //  }
-  //}
+  pub fn raise (&self) {
+    unsafe {
+      XRaiseWindow (self.display (), self.handle);
+    }
+  }
+
+  /// Asks the window manager to activate the window with a
+  /// `_NET_ACTIVE_WINDOW` message, which usually raises and focuses it.
+  pub fn activate (&self) -> bool {
+    // https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html#idm46463187634240
+    unsafe {
+      let root = XDefaultRootWindow (self.display ());
+      let name = CString::new ("_NET_ACTIVE_WINDOW").unwrap ();
+      let event = XEvent {
+        client_message: XClientMessageEvent {
+          type_: ClientMessage,
+          serial: 0,
+          send_event: TRUE,
+          display: self.display (),
+          window: self.handle,
+          message_type: XInternAtom (self.display (), name.as_ptr (), FALSE),
+          format: 32,
+          data: ClientMessageData::from ([
+            // Source indication, 2 for pagers and taskbars.
+            2,
+            CurrentTime as i64,
+            // The currently active window, we don't know it.
+            0,
+            0,
+            0,
+          ]),
+        },
+      };
+      Window::from_handle (&self.display (), root)
+        .send_event (event, SubstructureRedirectMask | SubstructureNotifyMask)
+    }
+  }
 
   pub fn map_raised (&self) {
     unsafe {
@@ -47,6 +80,12 @@ impl Window {
     }
   }
 
+  pub fn unmap (&self) {
+    unsafe {
+      XUnmapWindow (self.display (), self.handle);
+    }
+  }
+
   pub fn move_and_resize (&self, x: i32, y: i32, w: u32, h: u32) {
     unsafe {
       XMoveResizeWindow (self.display (), self.handle, x, y, w, h);
@@ -65,6 +104,158 @@ impl Window {
     }
   }
 
+  pub fn define_cursor (&self, cursor: Cursor) {
+    unsafe {
+      XDefineCursor (self.display (), self.handle, cursor);
+    }
+  }
+
+  pub fn select_input (&self, mask: c_long) {
+    unsafe {
+      XSelectInput (self.display (), self.handle, mask);
+    }
+  }
+
+  /// Waits up to `timeout` for a `ConfigureNotify` on this window and returns
+  /// the geometry it has after it, other events stay queued.  Requires
+  /// `StructureNotifyMask` to be selected.
+  pub fn wait_configure (&self, timeout: Duration) -> Option<(i32, i32, u32, u32)> {
+    let start = Instant::now ();
+    let mut event: XEvent = unsafe { std::mem::zeroed () };
+    loop {
+      unsafe {
+        if XCheckTypedWindowEvent (self.display (), self.handle, ConfigureNotify, &mut event)
+          != 0
+        {
+          break;
+        }
+      }
+      if start.elapsed () >= timeout {
+        return None;
+      }
+      std::thread::sleep (Duration::from_millis (5));
+    }
+    self.get_geometry ()
+  }
+
+  /// Returns the position of the window relative to the root window and its
+  /// size, or `None` if the window does not exist.
+  pub fn get_geometry (&self) -> Option<(i32, i32, u32, u32)> {
+    let mut root: XWindow = NONE;
+    let mut x: c_int = 0;
+    let mut y: c_int = 0;
+    let mut w: c_uint = 0;
+    let mut h: c_uint = 0;
+    // Dummy values
+    let mut u: c_uint = 0;
+    let mut child: XWindow = NONE;
+    unsafe {
+      if XGetGeometry (
+        self.display (),
+        self.handle,
+        &mut root,
+        &mut x,
+        &mut y,
+        &mut w,
+        &mut h,
+        &mut u,
+        &mut u,
+      ) == 0
+      {
+        return None;
+      }
+      XTranslateCoordinates (
+        self.display (),
+        self.handle,
+        root,
+        0,
+        0,
+        &mut x,
+        &mut y,
+        &mut child,
+      );
+    }
+    Some ((x, y, w, h))
+  }
+
+  pub fn children (&self) -> Vec<XWindow> {
+    let mut root: XWindow = NONE;
+    let mut parent: XWindow = NONE;
+    let mut children: *mut XWindow = std::ptr::null_mut ();
+    let mut count: c_uint = 0;
+    unsafe {
+      if XQueryTree (
+        self.display (),
+        self.handle,
+        &mut root,
+        &mut parent,
+        &mut children,
+        &mut count,
+      ) == 0
+        || children.is_null ()
+      {
+        return Vec::new ();
+      }
+      let result = std::slice::from_raw_parts (children, count as usize).to_vec ();
+      XFree (children as *mut c_void);
+      result
+    }
+  }
+
+  pub fn is_viewable (&self) -> bool {
+    unsafe {
+      let mut attributes: XWindowAttributes = std::mem::MaybeUninit::zeroed ().assume_init ();
+      XGetWindowAttributes (self.display (), self.handle, &mut attributes) != 0
+        && attributes.map_state == IsViewable
+    }
+  }
+
+  pub fn get_wm_normal_hints (&self) -> Option<XSizeHints> {
+    unsafe {
+      let mut hints: XSizeHints = std::mem::MaybeUninit::zeroed ().assume_init ();
+      let mut supplied: c_long = 0;
+      if XGetWMNormalHints (self.display (), self.handle, &mut hints, &mut supplied) != 0 {
+        Some (hints)
+      } else {
+        None
+      }
+    }
+  }
+
+  pub fn has_property (&self, property: Atom) -> bool {
+    get_window_property (self.display (), self.handle, property, AnyPropertyType as Atom).is_some ()
+  }
+
+  /// Returns the value of an atom list property, empty if the window does not
+  /// have the property.
+  pub fn get_atoms (&self, property: Atom) -> Vec<Atom> {
+    get_window_property (self.display (), self.handle, property, XA_ATOM)
+      .and_then (|property| property.items (XA_ATOM))
+      .unwrap_or_default ()
+  }
+
+  /// Returns the first window in the tree below and including this one that
+  /// has the `WM_STATE` property, which is set on client windows by the
+  /// window manager.  Windows closer to this one are preferred.
+  pub fn find_client (&self) -> Option<XWindow> {
+    let wm_state = unsafe {
+      let name = CString::new ("WM_STATE").unwrap ();
+      XInternAtom (self.display (), name.as_ptr (), FALSE)
+    };
+    let mut queue = std::collections::VecDeque::from ([self.handle]);
+    while let Some (handle) = queue.pop_front () {
+      let window = Self {
+        handle,
+        display: self.display,
+      };
+      if window.has_property (wm_state) {
+        return Some (handle);
+      }
+      queue.extend (window.children ());
+    }
+    None
+  }
+
   pub fn set_class_hint (&self, class: &str, name: &str) {
     unsafe {
       let class_cstr = std::ffi::CString::new (class).unwrap ();